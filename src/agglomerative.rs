@@ -0,0 +1,260 @@
+use rayon::prelude::*;
+
+use crate::kmeans::{Calculate, Kmeans};
+
+/// Linkage criterion for agglomerative merging of clusters.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Linkage {
+    /// Merge the clusters with the smallest increase in the sum of squared
+    /// errors.
+    Ward,
+    /// Distance between clusters is the size weighted average of the
+    /// differences of their members.
+    Average,
+    /// Distance between clusters is the largest difference of their members.
+    Complete,
+}
+
+/// A merge of two clusters in a [`Dendrogram`](struct.Dendrogram.html).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Merge {
+    /// First merged cluster.
+    pub first: usize,
+    /// Second merged cluster.
+    pub second: usize,
+    /// Linkage distance between the merged clusters.
+    pub distance: f32,
+    /// Number of points in the merged cluster.
+    pub size: usize,
+}
+
+/// Struct result of agglomerative merging of k-means centroids.
+///
+/// Clusters `0..leaves` are the original centroids and the `n`-th merge
+/// creates the cluster `leaves + n`.
+#[derive(Clone, Debug, Default)]
+pub struct Dendrogram {
+    /// Number of original centroids.
+    pub leaves: usize,
+    /// Number of points indexed to each original centroid.
+    pub sizes: Vec<usize>,
+    /// Merges in order from the closest to the furthest clusters.
+    pub merges: Vec<Merge>,
+}
+
+impl Dendrogram {
+    /// Cut the dendrogram into `clusters` clusters, returning the new label of
+    /// each original centroid. Labels are ordered by the smallest original
+    /// centroid of each cluster. `clusters` is clamped to the range of
+    /// available cluster counts.
+    pub fn cut(&self, clusters: usize) -> Vec<u8> {
+        let clusters: usize = clusters.clamp(1.min(self.leaves), self.leaves);
+
+        // Find the cluster each node ends up in after the applied merges
+        let mut parent: Vec<usize> = (0..self.leaves + self.merges.len()).collect();
+        self.merges
+            .iter()
+            .take(self.leaves - clusters)
+            .enumerate()
+            .for_each(|(n, merge): (usize, &Merge)| {
+                parent[merge.first] = self.leaves + n;
+                parent[merge.second] = self.leaves + n;
+            });
+        let root = |mut node: usize| -> usize {
+            while parent[node] != node {
+                node = parent[node];
+            }
+            node
+        };
+
+        let mut roots: Vec<usize> = Vec::with_capacity(clusters);
+        (0..self.leaves)
+            .map(|leaf: usize| {
+                let r: usize = root(leaf);
+                match roots.iter().position(|&x: &usize| x == r) {
+                    Some(label) => label as u8,
+                    None => {
+                        roots.push(r);
+                        (roots.len() - 1) as u8
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Relabel an indexed buffer for a cut into `clusters` clusters.
+    pub fn relabel(&self, indices: &[u8], clusters: usize) -> Vec<u8> {
+        let labels: Vec<u8> = self.cut(clusters);
+        indices
+            .par_iter()
+            .map(|&i: &u8| labels[i as usize])
+            .collect()
+    }
+
+    /// Cut a k-means result into `clusters` clusters. Merged centroids are the
+    /// mean of their original centroids weighted by the number of points, and
+    /// the indices are relabeled.
+    pub fn cut_kmeans<const N: usize>(
+        &self,
+        kmeans: &Kmeans<[f32; N]>,
+        clusters: usize,
+    ) -> Kmeans<[f32; N]> {
+        let labels: Vec<u8> = self.cut(clusters);
+        let count: usize = labels
+            .iter()
+            .map(|&l: &u8| l as usize + 1)
+            .max()
+            .unwrap_or(0);
+
+        let mut sums: Vec<([f32; N], f32)> = vec![([0.0; N], 0.0); count];
+        kmeans
+            .centroids
+            .iter()
+            .zip(labels.iter())
+            .zip(self.sizes.iter())
+            .for_each(|((centroid, &label), &size): ((&[f32; N], &u8), &usize)| {
+                let weight: f32 = size.max(1) as f32;
+                let (sum, total): &mut ([f32; N], f32) = &mut sums[label as usize];
+                sum.iter_mut()
+                    .zip(centroid.iter())
+                    .for_each(|(s, c): (&mut f32, &f32)| *s += weight * c);
+                *total += weight;
+            });
+
+        Kmeans {
+            score: kmeans.score,
+            centroids: sums
+                .iter()
+                .map(|(sum, total): &([f32; N], f32)| sum.map(|v: f32| v / total))
+                .collect(),
+            indices: kmeans
+                .indices
+                .par_iter()
+                .map(|&i: &u8| labels[i as usize])
+                .collect(),
+            locked: Vec::new(),
+        }
+    }
+}
+
+/// Merge the centroids of a k-means result bottom-up into a dendrogram.
+///
+/// Distances between centroids are calculated with
+/// [`Calculate::difference`](trait.Calculate.html#tymethod.difference) and
+/// clusters are weighted by the number of points indexed to each centroid.
+/// Centroids without points are weighted as a single point. Running k-means
+/// once with a generous `k` and cutting the dendrogram gives palettes at
+/// several resolutions from a single clustering pass.
+///
+/// ```
+/// use kmeans_colors::{merge_centroids, Kmeans, Linkage};
+///
+/// let mut kmeans = Kmeans::new();
+/// kmeans.centroids = vec![[0.0; 3], [10.0; 3], [200.0; 3], [220.0; 3]];
+/// kmeans.indices = vec![0, 0, 1, 2, 3];
+///
+/// // The closest pair is merged first
+/// let dendrogram = merge_centroids(&kmeans, Linkage::Complete);
+/// assert_eq!(dendrogram.sizes, [2, 1, 1, 1]);
+/// assert_eq!((dendrogram.merges[0].first, dendrogram.merges[0].second), (0, 1));
+/// assert_eq!(dendrogram.merges[0].distance, 300.0);
+/// assert_eq!(dendrogram.merges[1].distance, 1200.0);
+/// assert_eq!(dendrogram.cut(2), [0, 0, 1, 1]);
+///
+/// // Merged centroids are weighted by their number of points
+/// let cut = dendrogram.cut_kmeans(&kmeans, 2);
+/// assert_eq!(cut.centroids, [[10.0 / 3.0; 3], [210.0; 3]]);
+/// assert_eq!(cut.indices, [0, 0, 0, 1, 1]);
+/// ```
+///
+/// ## Reference
+///
+/// Lance, G. N., & Williams, W. T. (1967). A general theory of classificatory
+/// sorting strategies: 1. Hierarchical systems. The computer journal, 9(4),
+/// 373-380.
+pub fn merge_centroids<C: Calculate>(kmeans: &Kmeans<C>, linkage: Linkage) -> Dendrogram {
+    let k: usize = kmeans.centroids.len();
+    let mut sizes: Vec<usize> = vec![0; k];
+    kmeans
+        .indices
+        .iter()
+        .for_each(|&i: &u8| sizes[i as usize] += 1);
+
+    // Active clusters with their node id and weight
+    let mut active: Vec<(usize, f32)> = sizes
+        .iter()
+        .enumerate()
+        .map(|(i, &size): (usize, &usize)| (i, size.max(1) as f32))
+        .collect();
+    let mut node_sizes: Vec<usize> = sizes.clone();
+    let mut distances: Vec<Vec<f32>> = (0..k)
+        .map(|i: usize| {
+            (0..k)
+                .map(|j: usize| {
+                    let diff: f32 = C::difference(&kmeans.centroids[i], &kmeans.centroids[j]);
+                    match linkage {
+                        Linkage::Ward => {
+                            let (ni, nj): (f32, f32) = (active[i].1, active[j].1);
+                            ni * nj / (ni + nj) * diff
+                        }
+                        Linkage::Average | Linkage::Complete => diff,
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut merges: Vec<Merge> = Vec::with_capacity(k.saturating_sub(1));
+    while active.len() > 1 {
+        // Find the closest pair of active clusters
+        let (mut a, mut b, mut min): (usize, usize, f32) = (0, 1, f32::MAX);
+        (0..active.len()).for_each(|i: usize| {
+            (i + 1..active.len()).for_each(|j: usize| {
+                if distances[i][j] < min {
+                    (a, b, min) = (i, j, distances[i][j]);
+                }
+            });
+        });
+
+        let (na, nb): (f32, f32) = (active[a].1, active[b].1);
+        let size: usize = node_sizes[active[a].0] + node_sizes[active[b].0];
+        merges.push(Merge {
+            first: active[a].0,
+            second: active[b].0,
+            distance: min,
+            size,
+        });
+        node_sizes.push(size);
+
+        // Lance-Williams update of the distances to the merged cluster, stored
+        // in the slot of `a`
+        (0..active.len())
+            .filter(|&c: &usize| c != a && c != b)
+            .for_each(|c: usize| {
+                let nc: f32 = active[c].1;
+                let (dac, dbc): (f32, f32) = (distances[a][c], distances[b][c]);
+                let d: f32 = match linkage {
+                    Linkage::Ward => {
+                        ((na + nc) * dac + (nb + nc) * dbc - nc * min) / (na + nb + nc)
+                    }
+                    Linkage::Average => (na * dac + nb * dbc) / (na + nb),
+                    Linkage::Complete => dac.max(dbc),
+                };
+                distances[a][c] = d;
+                distances[c][a] = d;
+            });
+        active[a] = (k + merges.len() - 1, na + nb);
+
+        active.swap_remove(b);
+        distances.swap_remove(b);
+        distances.iter_mut().for_each(|row: &mut Vec<f32>| {
+            row.swap_remove(b);
+        });
+    }
+
+    Dendrogram {
+        leaves: k,
+        sizes,
+        merges,
+    }
+}
@@ -0,0 +1,314 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+use rand::{rngs::SmallRng, thread_rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::kmeans::{lloyd, Calculate, Kmeans};
+
+/// Cost of a path in the assignment flow network. Paths which fill a cluster
+/// below its minimum size carry a penalty of `-1` and are always preferred.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Cost {
+    penalty: i32,
+    value: f64,
+}
+
+impl Cost {
+    const INFINITY: Cost = Cost {
+        penalty: i32::MAX,
+        value: f64::INFINITY,
+    };
+
+    #[inline]
+    fn new(penalty: i32, value: f64) -> Self {
+        Cost { penalty, value }
+    }
+
+    #[inline]
+    fn add(self, other: Cost) -> Cost {
+        Cost::new(self.penalty + other.penalty, self.value + other.value)
+    }
+
+    #[inline]
+    fn less_than(&self, other: &Cost) -> bool {
+        match self.penalty.cmp(&other.penalty) {
+            Ordering::Less => true,
+            Ordering::Greater => false,
+            Ordering::Equal => self.value < other.value,
+        }
+    }
+}
+
+/// Cost of moving a point between clusters, ordered for a min-heap.
+#[derive(Copy, Clone, Debug)]
+struct Move {
+    cost: f64,
+    point: usize,
+}
+
+impl PartialEq for Move {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Move {}
+
+impl PartialOrd for Move {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Move {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cost
+            .total_cmp(&other.cost)
+            .then_with(|| self.point.cmp(&other.point))
+    }
+}
+
+/// Assign points to clusters minimizing the total cost subject to the size
+/// constraints, using successive shortest paths on the assignment flow
+/// network. Each point is routed to the sink through the cheapest chain of
+/// reassignments between clusters.
+fn assign_balanced(costs: &[f64], k: usize, min_size: usize, max_size: usize) -> Vec<u8> {
+    let n: usize = costs.len() / k;
+    let mut assignment: Vec<usize> = vec![usize::MAX; n];
+    let mut counts: Vec<usize> = vec![0; k];
+
+    // Heap of candidate moves for each pair of clusters `(from, to)`, entries
+    // are stale once their point has left `from`
+    let mut moves: Vec<BinaryHeap<Reverse<Move>>> = vec![BinaryHeap::new(); k * k];
+    let push_moves = |moves: &mut Vec<BinaryHeap<Reverse<Move>>>, point: usize, from: usize| {
+        let row: &[f64] = &costs[point * k..(point + 1) * k];
+        (0..k)
+            .filter(|&to: &usize| to != from)
+            .for_each(|to: usize| {
+                moves[from * k + to].push(Reverse(Move {
+                    cost: row[to] - row[from],
+                    point,
+                }));
+            });
+    };
+
+    let mut tops: Vec<Option<Move>> = vec![None; k * k];
+    let mut dist: Vec<Cost> = vec![Cost::INFINITY; k];
+    let mut pred: Vec<Option<(usize, usize)>> = vec![None; k];
+
+    (0..n).for_each(|p: usize| {
+        // Cheapest valid move between each pair of clusters
+        tops.iter_mut().zip(moves.iter_mut()).enumerate().for_each(
+            |(i, (top, heap)): (usize, (&mut Option<Move>, &mut BinaryHeap<Reverse<Move>>))| {
+                let from: usize = i / k;
+                while let Some(Reverse(m)) = heap.peek() {
+                    if assignment[m.point] == from {
+                        break;
+                    }
+                    heap.pop();
+                }
+                *top = heap.peek().map(|Reverse(m): &Reverse<Move>| *m);
+            },
+        );
+
+        // Bellman-Ford from the new point over the clusters
+        let row: &[f64] = &costs[p * k..(p + 1) * k];
+        dist.iter_mut()
+            .zip(row.iter())
+            .for_each(|(d, &c): (&mut Cost, &f64)| *d = Cost::new(0, c));
+        pred.iter_mut()
+            .for_each(|x: &mut Option<(usize, usize)>| *x = None);
+        for _ in 0..k {
+            let mut changed: bool = false;
+            (0..k).for_each(|from: usize| {
+                (0..k).for_each(|to: usize| {
+                    if let Some(m) = tops[from * k + to] {
+                        let candidate: Cost = dist[from].add(Cost::new(0, m.cost));
+                        if candidate.less_than(&dist[to]) {
+                            dist[to] = candidate;
+                            pred[to] = Some((from, m.point));
+                            changed = true;
+                        }
+                    }
+                });
+            });
+            if !changed {
+                break;
+            }
+        }
+
+        // Cheapest cluster with room to grow, filling minimum sizes first
+        let (end, _): (usize, Cost) = (0..k)
+            .filter_map(|j: usize| {
+                let sink: Cost = if counts[j] < min_size {
+                    Cost::new(-1, 0.0)
+                } else if counts[j] < max_size {
+                    Cost::new(0, 0.0)
+                } else {
+                    return None;
+                };
+                Some((j, dist[j].add(sink)))
+            })
+            .fold(
+                (usize::MAX, Cost::INFINITY),
+                |best: (usize, Cost), c: (usize, Cost)| {
+                    if c.1.less_than(&best.1) {
+                        c
+                    } else {
+                        best
+                    }
+                },
+            );
+        if end == usize::MAX {
+            return;
+        }
+
+        // Apply the chain of reassignments ending in `end`
+        let mut cluster: usize = end;
+        let mut steps: usize = 0;
+        while let Some((from, point)) = pred[cluster] {
+            assignment[point] = cluster;
+            push_moves(&mut moves, point, cluster);
+            cluster = from;
+            steps += 1;
+            if steps > k {
+                break;
+            }
+        }
+        assignment[p] = cluster;
+        push_moves(&mut moves, p, cluster);
+        counts[end] += 1;
+    });
+
+    assignment
+        .iter()
+        .map(|&a: &usize| a.min(k - 1) as u8)
+        .collect()
+}
+
+/// Find each point's centroid subject to cluster size constraints, index the
+/// point with that centroid.
+///
+/// The assignment minimizes the total
+/// [`Calculate::difference`](trait.Calculate.html#tymethod.difference) of
+/// points to their centroids while every cluster receives at least `min_size`
+/// and at most `max_size` points. It is solved exactly as a min-cost flow
+/// with successive shortest paths, which is slower than
+/// [`Calculate::get_closest_centroid`][closest].
+///
+/// [closest]: trait.Calculate.html#tymethod.get_closest_centroid
+///
+/// # Panics
+///
+/// Panics if the size constraints cannot be satisfied, i.e. the buffer has
+/// fewer than `centroids.len() * min_size` or more than
+/// `centroids.len() * max_size` points.
+pub fn get_closest_centroid_balanced<C: Calculate + Sync>(
+    buf: &[C],
+    centroids: &[C],
+    indices: &mut Vec<u8>,
+    min_size: usize,
+    max_size: usize,
+) {
+    let k: usize = centroids.len();
+    assert!(
+        k * min_size <= buf.len() && buf.len() <= k.saturating_mul(max_size),
+        "Cluster size constraints cannot be satisfied."
+    );
+
+    let costs: Vec<f64> = buf
+        .par_iter()
+        .flat_map_iter(|point: &C| {
+            centroids
+                .iter()
+                .map(move |c: &C| C::difference(point, c) as f64)
+        })
+        .collect();
+    indices.extend(assign_balanced(&costs, k, min_size, max_size));
+}
+
+/// Find the k-means centroids of a buffer with cluster size constraints. Takes
+/// the same arguments as [`get_kmeans`](fn.get_kmeans.html) along with the
+/// minimum and maximum number of points of each cluster.
+///
+/// Points are assigned with
+/// [`get_closest_centroid_balanced`](fn.get_closest_centroid_balanced.html) in
+/// place of the nearest centroid, so no cluster ends up representing only a
+/// handful of points. Setting `min_size` to `buf.len() / k` and `max_size` to
+/// `buf.len().div_ceil(k)` produces clusters as balanced as possible.
+///
+/// - `k` - number of clusters.
+/// - `min_size` - minimum number of points in each cluster.
+/// - `max_size` - maximum number of points in each cluster.
+/// - `max_iter` - maximum number of iterations.
+/// - `converge` - threshold for convergence.
+/// - `verbose` - flag for printing convergence information to console.
+/// - `buf` - array of points.
+///
+/// # Panics
+///
+/// Panics if the size constraints cannot be satisfied or if buffer is empty.
+///
+/// ```
+/// use kmeans_colors::get_kmeans_balanced;
+///
+/// // Two well separated groups of unequal size are split evenly
+/// let buf = [[0.0; 3], [1.0; 3], [2.0; 3], [255.0; 3]];
+/// let result = get_kmeans_balanced(2, 2, 2, 20, 0.0, false, &buf);
+/// assert_eq!(result.indices[0], result.indices[1]);
+/// assert_eq!(result.indices[2], result.indices[3]);
+/// assert_ne!(result.indices[0], result.indices[3]);
+///
+/// // Duplicate points still fill every cluster
+/// let buf = [[127.0; 3]; 8];
+/// let result = get_kmeans_balanced(2, 4, 4, 20, 0.001, false, &buf);
+/// assert_eq!(result.centroids, [[127.0; 3]; 2]);
+/// assert_eq!(result.indices.iter().filter(|&&i| i == 0).count(), 4);
+///
+/// // Clusters differ by at most one point when `k` does not divide the buffer
+/// let buf = [[0.0; 3], [1.0; 3], [2.0; 3], [3.0; 3], [255.0; 3]];
+/// let result = get_kmeans_balanced(2, 5 / 2, 5_usize.div_ceil(2), 20, 0.0, false, &buf);
+/// let small = result.indices.iter().filter(|&&i| i == result.indices[4]).count();
+/// assert_eq!(small, 2);
+/// ```
+///
+/// ## Reference
+///
+/// Bradley, P. S., Bennett, K. P., & Demiriz, A. (2000). Constrained k-means
+/// clustering. Microsoft Research, Redmond, 20.
+pub fn get_kmeans_balanced<C: Calculate + Clone + Sync + Send>(
+    k: usize,
+    min_size: usize,
+    max_size: usize,
+    max_iter: usize,
+    converge: f32,
+    verbose: bool,
+    buf: &[C],
+) -> Kmeans<C> {
+    // Initialize the random centroids
+    let mut rng: SmallRng = SmallRng::from_rng(thread_rng()).unwrap();
+    let mut centroids: Vec<C> = Vec::with_capacity(k);
+    crate::plus_plus::init_plus_plus(k, &mut rng, buf, &mut centroids);
+
+    // A buffer with fewer than `k` distinct points still needs `k` clusters to
+    // share its points, so duplicate centroids until there are `k`
+    let distinct: usize = centroids.len();
+    (distinct..k).for_each(|i: usize| {
+        let centroid: C = centroids[i % distinct].clone();
+        centroids.push(centroid);
+    });
+
+    lloyd(
+        centroids,
+        max_iter,
+        converge,
+        verbose,
+        buf,
+        &|c1: &C, c2: &C| C::difference(c1, c2),
+        |buf: &[C], centroids: &[C], indices: &mut Vec<u8>| {
+            get_closest_centroid_balanced(buf, centroids, indices, min_size, max_size)
+        },
+        C::recalculate_centroids,
+    )
+}
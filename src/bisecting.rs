@@ -0,0 +1,251 @@
+use rayon::prelude::*;
+
+use crate::kmeans::{get_kmeans, Calculate, Kmeans};
+
+/// Criterion for choosing which cluster to split next in bisecting k-means.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SplitCriterion {
+    /// Split the cluster with the most points.
+    Size,
+    /// Split the cluster with the largest sum of squared errors.
+    Sse,
+}
+
+/// A cluster in the split tree of bisecting k-means.
+#[derive(Clone, Debug)]
+pub struct BisectingNode<C: Calculate> {
+    /// Centroid of the cluster.
+    pub centroid: C,
+    /// Index of the parent node, `None` for the root.
+    pub parent: Option<usize>,
+    /// Indices of the two child nodes if the cluster was split.
+    pub children: Option<(usize, usize)>,
+    /// Number of points in the cluster.
+    pub size: usize,
+    /// Sum of the differences between the points and the centroid.
+    pub sse: f32,
+}
+
+/// Struct result of bisecting k-means containing the full split tree.
+///
+/// Nodes are stored in the order they were created. The root is the first
+/// node and the `n`-th split created the nodes `2n + 1` and `2n + 2`, so the
+/// clustering with `k` clusters consists of the first `2k - 1` nodes.
+#[derive(Clone, Debug)]
+pub struct BisectingTree<C: Calculate> {
+    /// Nodes of the split tree.
+    pub nodes: Vec<BisectingNode<C>>,
+    /// Leaf node of each point of the buffer in the fully split tree.
+    pub leaves: Vec<usize>,
+}
+
+impl<C: Calculate + Clone + Sync> BisectingTree<C> {
+    /// Returns the largest number of clusters the tree can be cut into.
+    pub fn max_clusters(&self) -> usize {
+        self.nodes.len().div_ceil(2)
+    }
+
+    /// Cut the tree into `k` clusters, returning a `Kmeans` result whose
+    /// centroids are ordered by node index. The `score` is the total sum of
+    /// squared errors of the clusters. `k` is clamped to the range of
+    /// available cluster counts.
+    pub fn cut(&self, k: usize) -> Kmeans<C> {
+        let k: usize = k.clamp(1, self.max_clusters().max(1));
+        let node_count: usize = 2 * k - 1;
+
+        // Nodes present in the cut that were not split within it
+        let mut labels: Vec<Option<u8>> = vec![None; node_count.min(self.nodes.len())];
+        let mut centroids: Vec<C> = Vec::with_capacity(k);
+        let mut score: f32 = 0.0;
+        self.nodes.iter().take(node_count).enumerate().for_each(
+            |(i, node): (usize, &BisectingNode<C>)| {
+                let split: bool = matches!(node.children, Some((a, _)) if a < node_count);
+                if !split {
+                    labels[i] = Some(centroids.len() as u8);
+                    centroids.push(node.centroid.clone());
+                    score += node.sse;
+                }
+            },
+        );
+
+        let indices: Vec<u8> = self
+            .leaves
+            .par_iter()
+            .map(|&leaf: &usize| {
+                let mut node: usize = leaf;
+                while node >= node_count {
+                    node = self.nodes[node].parent.unwrap_or(0);
+                }
+                labels[node].unwrap_or(0)
+            })
+            .collect();
+
+        Kmeans {
+            score,
+            centroids,
+            indices,
+            locked: Vec::new(),
+        }
+    }
+}
+
+/// Sum of the differences between points and a centroid.
+fn sse<C: Calculate + Sync>(buf: &[C], members: &[usize], centroid: &C) -> f32 {
+    members
+        .par_iter()
+        .map(|&i: &usize| C::difference(&buf[i], centroid))
+        .sum()
+}
+
+/// Build a hierarchical clustering of a buffer with bisecting k-means.
+///
+/// Starting from a single cluster of all points, the cluster chosen by
+/// `criterion` is repeatedly split in two with
+/// [`get_kmeans`](fn.get_kmeans.html) until there are `k` clusters or no
+/// cluster can be split. The returned tree can be
+/// [`cut`](struct.BisectingTree.html#method.cut) into any number of clusters
+/// up to `k` without recomputation.
+///
+/// - `k` - maximum number of clusters.
+/// - `criterion` - criterion for choosing the cluster to split.
+/// - `max_iter` - maximum number of iterations of each split.
+/// - `converge` - threshold for convergence of each split.
+/// - `verbose` - flag for printing split information to console.
+/// - `buf` - array of points.
+///
+/// # Panics
+///
+/// Panics if buffer is empty.
+///
+/// ```
+/// use kmeans_colors::{get_bisecting_kmeans, SplitCriterion};
+///
+/// let buf = [[0.0; 3], [1.0; 3], [10.0; 3], [11.0; 3], [200.0; 3], [201.0; 3]];
+/// let tree = get_bisecting_kmeans(3, SplitCriterion::Sse, 20, 0.0, false, &buf);
+/// assert_eq!(tree.max_clusters(), 3);
+///
+/// // The distant group is split off first
+/// let mut two = tree.cut(2);
+/// two.centroids.sort_by(|a, b| a[0].total_cmp(&b[0]));
+/// assert_eq!(two.centroids, [[5.5; 3], [200.5; 3]]);
+///
+/// // Then the cluster with the largest error is split
+/// let mut three = tree.cut(3);
+/// assert_eq!(three.score, 4.5);
+/// three.centroids.sort_by(|a, b| a[0].total_cmp(&b[0]));
+/// assert_eq!(three.centroids, [[0.5; 3], [10.5; 3], [200.5; 3]]);
+/// ```
+///
+/// ## Reference
+///
+/// Steinbach, M., Karypis, G., & Kumar, V. (2000). A comparison of document
+/// clustering techniques. In: KDD workshop on text mining.
+pub fn get_bisecting_kmeans<C: Calculate + Clone + Sync + Send>(
+    k: usize,
+    criterion: SplitCriterion,
+    max_iter: usize,
+    converge: f32,
+    verbose: bool,
+    buf: &[C],
+) -> BisectingTree<C> {
+    assert!(!buf.is_empty());
+
+    let root: Vec<usize> = (0..buf.len()).collect();
+    let root_centroid: C = get_kmeans(1, max_iter, converge, false, buf)
+        .centroids
+        .swap_remove(0);
+    let mut nodes: Vec<BisectingNode<C>> = vec![BisectingNode {
+        size: root.len(),
+        sse: sse(buf, &root, &root_centroid),
+        centroid: root_centroid,
+        parent: None,
+        children: None,
+    }];
+
+    // Members of each leaf that can still be split
+    let mut leaves: Vec<(usize, Vec<usize>)> = vec![(0, root)];
+    let mut final_leaves: Vec<(usize, Vec<usize>)> = Vec::new();
+
+    while nodes.len() < 2 * k.max(1) - 1 {
+        // Choose the leaf to split
+        let chosen: Option<usize> = leaves
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, members))| members.len() > 1)
+            .max_by(|(_, (a, _)), (_, (b, _))| match criterion {
+                SplitCriterion::Size => nodes[*a].size.cmp(&nodes[*b].size),
+                SplitCriterion::Sse => nodes[*a]
+                    .sse
+                    .partial_cmp(&nodes[*b].sse)
+                    .unwrap_or(core::cmp::Ordering::Equal),
+            })
+            .map(|(i, _)| i);
+        let (node, members): (usize, Vec<usize>) = match chosen {
+            Some(i) => leaves.swap_remove(i),
+            None => break,
+        };
+
+        let points: Vec<C> = members.iter().map(|&i: &usize| buf[i].clone()).collect();
+        let result: Kmeans<C> = get_kmeans(2, max_iter, converge, false, &points);
+        let mut left: Vec<usize> = Vec::new();
+        let mut right: Vec<usize> = Vec::new();
+        members
+            .iter()
+            .zip(result.indices.iter())
+            .for_each(|(&i, &index): (&usize, &u8)| {
+                if index == 0 {
+                    left.push(i);
+                } else {
+                    right.push(i);
+                }
+            });
+
+        // Identical points cannot be split
+        if result.centroids.len() < 2 || left.is_empty() || right.is_empty() {
+            final_leaves.push((node, members));
+            continue;
+        }
+
+        if verbose {
+            println!(
+                "Split: node {} into {} and {} points",
+                node,
+                left.len(),
+                right.len()
+            );
+        }
+
+        let first: usize = nodes.len();
+        nodes[node].children = Some((first, first + 1));
+        result
+            .centroids
+            .into_iter()
+            .zip([left, right])
+            .enumerate()
+            .for_each(|(j, (centroid, part)): (usize, (C, Vec<usize>))| {
+                nodes.push(BisectingNode {
+                    size: part.len(),
+                    sse: sse(buf, &part, &centroid),
+                    centroid,
+                    parent: Some(node),
+                    children: None,
+                });
+                leaves.push((first + j, part));
+            });
+    }
+
+    let mut point_leaves: Vec<usize> = vec![0; buf.len()];
+    leaves
+        .iter()
+        .chain(final_leaves.iter())
+        .for_each(|(node, members): &(usize, Vec<usize>)| {
+            members
+                .iter()
+                .for_each(|&i: &usize| point_leaves[i] = *node);
+        });
+
+    BisectingTree {
+        nodes,
+        leaves: point_leaves,
+    }
+}
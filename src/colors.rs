@@ -1,4 +1,7 @@
+mod dither;
 mod kmeans;
 mod sort;
 
+pub(crate) use self::dither::{error_diffusion, nearest};
 pub use self::kmeans::MapColor;
+pub use self::sort::sort_permutation;
@@ -0,0 +1,146 @@
+use rayon::prelude::*;
+
+use crate::dither::{Dither, ErrorDiffusion, OrderedDither};
+use crate::kmeans::Calculate;
+
+/// Find the index of the palette color nearest to a color.
+#[inline]
+pub(crate) fn nearest<const N: usize>(color: &[f32; N], palette: &[[f32; N]]) -> u8 {
+    palette
+        .iter()
+        .enumerate()
+        .fold(
+            (0, f32::INFINITY),
+            |(idx, min): (usize, f32), (j, c): (usize, &[f32; N])| {
+                let diff: f32 = <[f32; N]>::difference(color, c);
+                if diff < min {
+                    (j, diff)
+                } else {
+                    (idx, min)
+                }
+            },
+        )
+        .0 as u8
+}
+
+impl<const N: usize> Dither for [f32; N] {
+    fn dither_error_diffusion(
+        buf: &[Self],
+        width: usize,
+        palette: &[Self],
+        options: &ErrorDiffusion,
+    ) -> Vec<u8> {
+        error_diffusion(buf, width, palette, options, None)
+    }
+
+    fn dither_ordered(
+        buf: &[Self],
+        width: usize,
+        palette: &[Self],
+        options: &OrderedDither,
+    ) -> Vec<u8> {
+        assert!(width > 0);
+        assert!(!palette.is_empty());
+
+        let spread: f32 = options.spread.unwrap_or_else(|| palette_spread(palette));
+        let (size, thresholds): (usize, Vec<f32>) = options.map.thresholds();
+
+        buf.par_iter()
+            .enumerate()
+            .map(|(i, color): (usize, &Self)| {
+                let (x, y): (usize, usize) = (i % width, i / width);
+                let offset: f32 = spread * (thresholds[(y % size) * size + x % size] - 0.5);
+                nearest(&color.map(|c: f32| c + offset), palette)
+            })
+            .collect()
+    }
+}
+
+/// Error diffusion dithering of an image buffer. Pixels outside of `mask` are
+/// skipped, they are not indexed and neither receive nor diffuse error.
+pub(crate) fn error_diffusion<const N: usize>(
+    buf: &[[f32; N]],
+    width: usize,
+    palette: &[[f32; N]],
+    options: &ErrorDiffusion,
+    mask: Option<&[bool]>,
+) -> Vec<u8> {
+    assert!(width > 0);
+    assert!(!palette.is_empty());
+
+    let weights: &[(isize, usize, f32)] = options.kernel.weights();
+    let rows: usize = weights.iter().map(|&(_, dy, _)| dy).max().unwrap_or(0) + 1;
+
+    // Accumulated error of the next rows, padded by two pixels on each side
+    let padded: usize = width + 4;
+    let mut errors: Vec<[f32; N]> = vec![[0.0; N]; padded * rows];
+    let mut indices: Vec<u8> = vec![0; buf.len()];
+
+    buf.chunks(width)
+        .enumerate()
+        .for_each(|(y, row): (usize, &[[f32; N]])| {
+            let reverse: bool = options.serpentine && y % 2 == 1;
+            let base: usize = (y % rows) * padded;
+
+            (0..row.len()).for_each(|i: usize| {
+                let x: usize = if reverse { row.len() - 1 - i } else { i };
+                if matches!(mask, Some(mask) if !mask[y * width + x]) {
+                    return;
+                }
+                let error: [f32; N] = errors[base + x + 2];
+                let mut color: [f32; N] = row[x];
+                color
+                    .iter_mut()
+                    .zip(error.iter())
+                    .for_each(|(c, e): (&mut f32, &f32)| *c += e);
+
+                let index: u8 = nearest(&color, palette);
+                indices[y * width + x] = index;
+
+                let mut diff: [f32; N] = color;
+                diff.iter_mut()
+                    .zip(palette[index as usize].iter())
+                    .for_each(|(d, p): (&mut f32, &f32)| *d = (*d - p) * options.strength);
+
+                weights
+                    .iter()
+                    .for_each(|&(dx, dy, weight): &(isize, usize, f32)| {
+                        let dx: isize = if reverse { -dx } else { dx };
+                        let target: usize =
+                            ((y + dy) % rows) * padded + (x as isize + 2 + dx) as usize;
+                        errors[target]
+                            .iter_mut()
+                            .zip(diff.iter())
+                            .for_each(|(e, d): (&mut f32, &f32)| *e += weight * d);
+                    });
+            });
+
+            // The current row becomes the furthest row ahead
+            errors[base..base + padded]
+                .iter_mut()
+                .for_each(|e: &mut [f32; N]| *e = [0.0; N]);
+        });
+
+    indices
+}
+
+/// Mean distance between each palette color and its nearest other color.
+fn palette_spread<const N: usize>(palette: &[[f32; N]]) -> f32 {
+    if palette.len() < 2 {
+        return 0.0;
+    }
+    palette
+        .iter()
+        .enumerate()
+        .map(|(i, a): (usize, &[f32; N])| {
+            palette
+                .iter()
+                .enumerate()
+                .filter(|&(j, _): &(usize, &[f32; N])| i != j)
+                .map(|(_, b): (usize, &[f32; N])| <[f32; N]>::difference(a, b))
+                .fold(f32::INFINITY, f32::min)
+                .sqrt()
+        })
+        .sum::<f32>()
+        / palette.len() as f32
+}
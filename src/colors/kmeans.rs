@@ -1,14 +1,24 @@
-use rand::{Rng, thread_rng};
+use rand::{thread_rng, Rng};
 use rayon::prelude::*;
 
 use crate::kmeans::{Calculate, Hamerly, HamerlyCentroids, HamerlyPoint};
 
-impl Calculate for [f32; 3] {
-    fn get_closest_centroid(rgb: &[[f32; 3]], centroids: &[[f32; 3]], indices: &mut Vec<u8>) {
-        rgb.into_iter().for_each(|&color: &[f32; 3]| {
+/// Add two points channel by channel.
+#[inline]
+fn add<const N: usize>(c1: [f32; N], c2: &[f32; N]) -> [f32; N] {
+    let mut sum: [f32; N] = c1;
+    sum.iter_mut()
+        .zip(c2.iter())
+        .for_each(|(a, b): (&mut f32, &f32)| *a += b);
+    sum
+}
+
+impl<const N: usize> Calculate for [f32; N] {
+    fn get_closest_centroid(buf: &[[f32; N]], centroids: &[[f32; N]], indices: &mut Vec<u8>) {
+        buf.iter().for_each(|color: &[f32; N]| {
             let index: u8 = centroids
                 .into_par_iter()
-                .map(|c: &[f32; 3]| Self::difference(&color, c))
+                .map(|c: &[f32; N]| Self::difference(color, c))
                 .enumerate()
                 .reduce(
                     || (0, f32::INFINITY),
@@ -25,76 +35,59 @@ impl Calculate for [f32; 3] {
         });
     }
 
-    fn recalculate_centroids(
-        buf: &[[f32; 3]],
-        centroids: &mut [[f32; 3]],
-        indices: &[u8],
-    ) {
+    fn recalculate_centroids(buf: &[[f32; N]], centroids: &mut [[f32; N]], indices: &[u8]) {
         centroids
             .par_iter_mut()
             .enumerate()
-            .for_each(|(i, centroid): (usize, &mut [f32; 3])| {
-                let (red, green, blue, count): (f32, f32, f32, i32) = indices
+            .for_each(|(i, centroid): (usize, &mut [f32; N])| {
+                let (sum, count): ([f32; N], i32) = indices
                     .into_par_iter()
                     .zip(buf.into_par_iter())
                     .filter(|(&index, _)| index == i as u8)
                     .fold(
-                        || (0.0, 0.0, 0.0, 0),
-                        |(r, g, b, c): (f32, f32, f32, i32), (_, color)| {
-                            (r + color[0], g + color[1], b + color[2], c + 1)
-                        },
+                        || ([0.0; N], 0),
+                        |(sum, c): ([f32; N], i32), (_, color)| (add(sum, color), c + 1),
                     )
                     .reduce(
-                        || (0.0, 0.0, 0.0, 0),
-                        |(r1, g1, b1, c1): (f32, f32, f32, i32),
-                         (r2, g2, b2, c2): (f32, f32, f32, i32)| {
-                            (r1 + r2, g1 + g2, b1 + b2, c1 + c2)
+                        || ([0.0; N], 0),
+                        |(s1, c1): ([f32; N], i32), (s2, c2): ([f32; N], i32)| {
+                            (add(s1, &s2), c1 + c2)
                         },
                     );
 
                 if count != 0 {
-                    *centroid = [
-                        red / count as f32,
-                        green / count as f32,
-                        blue / count as f32,
-                    ];
+                    *centroid = sum.map(|v: f32| v / count as f32);
                 } else {
-                    let mut trng = thread_rng();
-                    *centroid = [
-                        trng.gen_range(0.0..255.0),
-                        trng.gen_range(0.0..255.0),
-                        trng.gen_range(0.0..255.0),
-                    ];
+                    *centroid = Self::create_random(&mut thread_rng());
                 }
             });
     }
 
-    fn check_loop(centroids: &[[f32; 3]], old_centroids: &[[f32; 3]]) -> f32 {
+    fn check_loop(centroids: &[[f32; N]], old_centroids: &[[f32; N]]) -> f32 {
         centroids
             .into_par_iter()
             .zip(old_centroids.into_par_iter())
-            .map(|(c1, c2): (&[f32; 3], &[f32; 3])| Self::difference(c1, c2))
+            .map(|(c1, c2): (&[f32; N], &[f32; N])| Self::difference(c1, c2))
             .sum::<f32>()
     }
 
     #[inline]
-    fn create_random(rng: &mut impl Rng) -> [f32; 3] {
-        [
-            rng.gen_range(0.0..255.0),
-            rng.gen_range(0.0..255.0),
-            rng.gen_range(0.0..255.0),
-        ]
+    fn create_random(rng: &mut impl Rng) -> [f32; N] {
+        [0.0; N].map(|_| rng.gen_range(0.0..255.0))
     }
 
     #[inline]
-    fn difference(c1: &[f32; 3], c2: &[f32; 3]) -> f32 {
-        (c1[0] - c2[0]).powf(2.0) + (c1[1] - c2[1]).powf(2.0) + (c1[2] - c2[2]).powf(2.0)
+    fn difference(c1: &[f32; N], c2: &[f32; N]) -> f32 {
+        c1.iter()
+            .zip(c2.iter())
+            .map(|(a, b): (&f32, &f32)| (a - b).powf(2.0))
+            .sum()
     }
 }
 
-impl Hamerly for [f32; 3] {
+impl<const N: usize> Hamerly for [f32; N] {
     fn compute_half_distances(centers: &mut HamerlyCentroids<Self>) {
-        let centroids: &Vec<[f32; 3]> = &centers.centroids;
+        let centroids: &Vec<[f32; N]> = &centers.centroids;
         centers.half_distances.par_iter_mut().enumerate().for_each(
             |(idx, half_dist): (usize, &mut f32)| {
                 let min_diff: f32 = (0..centroids.len()).filter(|&jdx: &usize| idx != jdx).fold(
@@ -114,79 +107,49 @@ impl Hamerly for [f32; 3] {
         centers: &HamerlyCentroids<Self>,
         points: &mut [HamerlyPoint],
     ) {
-        points.par_iter_mut().for_each(|point: &mut HamerlyPoint| {
-            // Assign max of lower bound and half distance to z
-            let z: f32 = centers.half_distances[point.index as usize].max(point.lower_bound);
+        points.par_iter_mut().zip(buffer.par_iter()).for_each(
+            |(point, color): (&mut HamerlyPoint, &[f32; N])| {
+                // Assign max of lower bound and half distance to z
+                let z: f32 = centers.half_distances[point.index as usize].max(point.lower_bound);
 
-            if point.upper_bound <= z {
-                return;
-            }
+                if point.upper_bound <= z {
+                    return;
+                }
 
-            // Tighten upper bound
-            let centroid: &[f32; 3] = &centers.centroids[point.index as usize];
-            point.upper_bound = Self::difference(&buffer[point.index as usize], centroid).sqrt();
+                // Tighten upper bound
+                let centroid: &[f32; N] = &centers.centroids[point.index as usize];
+                point.upper_bound = Self::difference(color, centroid).sqrt();
 
-            if point.upper_bound <= z {
-                return;
-            }
+                if point.upper_bound <= z {
+                    return;
+                }
 
-            // Find the two closest centers to current point and their distances
-            if centers.centroids.len() < 2 {
-                return;
-            }
+                // Find the two closest centers to current point and their distances
+                if centers.centroids.len() < 2 {
+                    return;
+                }
 
-            let (min1, c1): (f32, usize) = centers
-                .centroids
-                .par_iter()
-                .enumerate()
-                .skip(1)
-                .fold(
-                    || {
-                        (
-                            Self::difference(&buffer[point.index as usize], &centers.centroids[0]),
-                            0,
-                        )
-                    },
-                    |(min, idx): (f32, usize), (j, centroid): (usize, &[f32; 3])| {
-                        let diff: f32 = Self::difference(&buffer[point.index as usize], centroid);
-                        if diff < min {
-                            (diff, j)
-                        } else {
-                            (min, idx)
-                        }
-                    },
-                )
-                .reduce(
-                    || (f32::MAX, 0),
-                    |(a, ia): (f32, usize), (b, ib): (f32, usize)| {
-                        if a < b {
-                            (a, ia)
-                        } else {
-                            (b, ib)
+                let (mut min1, mut min2, mut c1): (f32, f32, usize) = (f32::MAX, f32::MAX, 0);
+                centers.centroids.iter().enumerate().for_each(
+                    |(j, centroid): (usize, &[f32; N])| {
+                        let diff: f32 = Self::difference(color, centroid);
+                        if diff < min1 {
+                            min2 = min1;
+                            min1 = diff;
+                            c1 = j;
+                        } else if diff < min2 {
+                            min2 = diff;
                         }
                     },
                 );
 
-            let mut min2 = f32::MAX;
-            centers
-                .centroids
-                .iter()
-                .enumerate()
-                .for_each(|(j, centroid): (usize, &[f32; 3])| {
-                    if j != c1 && j != point.index as usize {
-                        let diff: f32 = Self::difference(&buffer[point.index as usize], centroid);
-                        if diff < min2 {
-                            min2 = diff;
-                        }
-                    }
-                });
-
-            if c1 != point.index as usize {
-                point.index = c1 as u8;
-                point.upper_bound = min1.sqrt();
-            }
-            point.lower_bound = min2.sqrt();
-        });
+                if c1 != point.index as usize {
+                    point.index = c1 as u8;
+                    point.upper_bound = min1.sqrt();
+                }
+                point.lower_bound = min2.sqrt();
+            },
+        );
     }
 
     fn recalculate_centroids_hamerly(
@@ -196,31 +159,23 @@ impl Hamerly for [f32; 3] {
         points: &[HamerlyPoint],
     ) {
         (0..centers.centroids.len()).for_each(|idx: usize| {
-            let mut red: f32 = 0.0;
-            let mut green: f32 = 0.0;
-            let mut blue: f32 = 0.0;
+            let mut sum: [f32; N] = [0.0; N];
             let mut counter: u64 = 0;
-            (0..points.len()).for_each(|jdx: usize| {
-                if points[jdx].index == idx as u8 {
-                    red += buf[jdx][0];
-                    green += buf[jdx][1];
-                    blue += buf[jdx][2];
+            points
+                .iter()
+                .zip(buf.iter())
+                .filter(|(point, _): &(&HamerlyPoint, &[f32; N])| point.index == idx as u8)
+                .for_each(|(_, color): (&HamerlyPoint, &[f32; N])| {
+                    sum = add(sum, color);
                     counter += 1;
-                }
-            });
-            if counter != 0 {
-                let new_color: [f32; 3] = [
-                    red / (counter as f32),
-                    green / (counter as f32),
-                    blue / (counter as f32),
-                ];
-                centers.deltas[idx] = Self::difference(&centers.centroids[idx], &new_color).sqrt();
-                centers.centroids[idx] = new_color;
+                });
+            let new_color: [f32; N] = if counter != 0 {
+                sum.map(|v: f32| v / counter as f32)
             } else {
-                let new_color: [f32; 3] = Self::create_random(&mut rng);
-                centers.deltas[idx] = Self::difference(&centers.centroids[idx], &new_color).sqrt();
-                centers.centroids[idx] = new_color;
-            }
+                Self::create_random(&mut rng)
+            };
+            centers.deltas[idx] = Self::difference(&centers.centroids[idx], &new_color).sqrt();
+            centers.centroids[idx] = new_color;
         });
     }
 
@@ -243,7 +198,7 @@ pub trait MapColor: Sized {
     fn map_indices_to_centroids(centroids: &[Self], indices: &[u8]) -> Vec<Self>;
 }
 
-impl MapColor for [f32; 3] {
+impl<const N: usize> MapColor for [f32; N] {
     #[inline]
     fn map_indices_to_centroids(centroids: &[Self], indices: &[u8]) -> Vec<Self> {
         indices
@@ -1,6 +1,6 @@
 use std::cmp::Ordering;
 
-use crate::sort::{CentroidData, Sort};
+use crate::sort::{CentroidData, Sort, SortOrder};
 use rayon::prelude::*;
 
 impl Sort for [f32; 3] {
@@ -10,98 +10,234 @@ impl Sort for [f32; 3] {
             .map(|res| res.centroid)
     }
 
-    fn sort_colors(centroids: &[Self]) -> Vec<Self> {
-        let mut rgb_colors: Vec<[f32; 3]> = centroids.to_vec();
-        let is_percentage =  centroids.par_iter().any(|rgb: &[f32; 3]| rgb.par_iter().any(|v| *v > 1.0));
-
-        if !is_percentage {
-            rgb_colors
-                .par_iter_mut()
-                .map(|rgb: &mut [f32; 3]| [rgb[0] / 255.0, rgb[0] / 255.0, rgb[0] / 255.0])
-                .collect::<Vec<[f32; 3]>>();
-        }
-
-        let mut hsl_colors: Vec<[f32; 3]> = rgb_colors
-            .par_iter()
-            .map(|rgb: &[f32; 3]| {
-                let (max_value, min_value): (f32, f32) = rgb.iter().fold(
-                    (f32::NEG_INFINITY, f32::INFINITY),
-                    |(max, min): (f32, f32), &val: &f32| (max.max(val), min.min(val)),
-                );
-                let luminance: f32 = (max_value + min_value) / 2.0;
-                let saturation: f32 = match max_value == min_value {
-                    true => 0.0,
-                    false if luminance <= 0.5 => (max_value - min_value) / (max_value + min_value),
-                    false => (max_value - min_value) / (2.0 - max_value - min_value),
-                };
-                let mut hue: f32 = if max_value == rgb[0] {
-                    ((rgb[1] - rgb[2]) / (max_value - min_value)) * 60.0
-                } else if max_value == rgb[1] {
-                    (2.0 + (rgb[2] - rgb[0]) / (max_value - min_value)) * 60.0
-                } else {
-                    (4.0 + (rgb[0] - rgb[1]) / (max_value - min_value)) * 60.0
-                };
+    fn sort_indexed_colors(centroids: &[Self], indices: &[u8]) -> Vec<CentroidData<Self>> {
+        let mut data: Vec<CentroidData<Self>> = CentroidData::from_indices(centroids, indices);
 
-                if hue.is_sign_negative() {
-                    hue += 360.0;
-                }
+        // Sort by CIELAB lightness
+        let scale: f32 = rgb_scale(centroids);
+        let lightness = |rgb: &[f32; 3]| -> f32 { srgb_to_lab(rgb.map(|v: f32| v / scale))[0] };
+        data.sort_unstable_by(|a: &CentroidData<Self>, b: &CentroidData<Self>| {
+            lightness(&a.centroid)
+                .partial_cmp(&lightness(&b.centroid))
+                .unwrap_or(Ordering::Equal)
+        });
+        data
+    }
 
-                [hue, saturation, luminance]
-            })
-            .collect();
+    fn sort_colors(centroids: &[Self]) -> Vec<Self> {
+        sort_permutation(centroids, &[], SortOrder::Hsl)
+            .iter()
+            .map(|&i: &u8| centroids[i as usize])
+            .collect()
+    }
+}
 
-        hsl_colors.par_sort_by(|a: &[f32; 3], b: &[f32; 3]| {
-            a[1].partial_cmp(&b[1])
+/// Returns the permutation that sorts `[f32; 3]` centroids in `order`, the
+/// `n`-th element is the index of the centroid that moves to position `n`. The
+/// indexed buffer is only used for [`SortOrder::Share`][share]. Apply the
+/// permutation with [`Kmeans::reorder`][reorder] or [`relabel`](fn.relabel.html).
+///
+/// [share]: enum.SortOrder.html#variant.Share
+/// [reorder]: struct.Kmeans.html#method.reorder
+pub fn sort_permutation(centroids: &[[f32; 3]], indices: &[u8], order: SortOrder) -> Vec<u8> {
+    let scale: f32 = rgb_scale(centroids);
+    let lab: Vec<[f32; 3]> = centroids
+        .iter()
+        .map(|rgb: &[f32; 3]| srgb_to_lab(rgb.map(|v: f32| v / scale)))
+        .collect();
+    let chroma = |c: &[f32; 3]| -> f32 { c[1].hypot(c[2]) };
+    let by = |key: &dyn Fn(usize) -> f32| -> Vec<u8> {
+        let mut permutation: Vec<u8> = (0..centroids.len()).map(|i: usize| i as u8).collect();
+        permutation.sort_by(|&a: &u8, &b: &u8| {
+            key(a as usize)
+                .partial_cmp(&key(b as usize))
                 .unwrap_or(Ordering::Equal)
-                .then_with(|| a[0].partial_cmp(&b[0]).unwrap_or(Ordering::Equal))
-                .then_with(|| a[2].partial_cmp(&b[2]).unwrap_or(Ordering::Equal))
         });
+        permutation
+    };
 
-        hsl_colors
-            .par_iter_mut()
-            .map(|hsl: &mut [f32; 3]| {
-                if hsl[1] == 0.0 {
-                    return [hsl[2] * 255.0; 3];
-                }
-
-                let tmp1: f32 = if hsl[2] < 0.5 {
-                    hsl[2] * (1.0 + hsl[1])
+    match order {
+        SortOrder::Lightness => by(&|i: usize| lab[i][0]),
+        SortOrder::Hue => {
+            // Grays sort before every hue angle in `0.0..360.0`
+            by(&|i: usize| {
+                if chroma(&lab[i]) < GRAY_CHROMA {
+                    lab[i][0] - 1000.0
                 } else {
-                    hsl[2] + hsl[1] - hsl[2] * hsl[1]
-                };
-
-                let tmp2: f32 = 2.0 * hsl[2] - tmp1;
-
-                hsl[0] /= 360.0;
-
-                let tmp_rgb: [f32; 3] = [hsl[0] + 0.333, hsl[0], hsl[0] - 0.333]
-                    .map(|v: f32| {
-                        if v > 1.0 {
-                            return v - 1.0;
-                        } else if v < 0.0 {
-                            return v + 1.0;
-                        } else {
-                            return v;
-                        }
-                    })
-                    .map(|v: f32| {
-                        if v * 6.0 < 1.0 {
-                            tmp2 + (tmp1 - tmp2) * 6.0 * v
-                        } else if v * 2.0 < 1.0 {
-                            tmp1
-                        } else if v * 3.0 < 2.0 {
-                            tmp2 + (tmp1 - tmp2) * (0.666 - v) * 6.0
-                        } else {
-                            tmp2
-                        }
-                    });
-
-                if !is_percentage {
-                    tmp_rgb.map(|v: f32| v * 255.0);
+                    lab[i][2].atan2(lab[i][1]).to_degrees().rem_euclid(360.0)
                 }
-
-                tmp_rgb
             })
-            .collect()
+        }
+        SortOrder::Share => {
+            let data: Vec<CentroidData<[f32; 3]>> = CentroidData::from_indices(centroids, indices);
+            by(&|i: usize| -data[i].percentage)
+        }
+        SortOrder::Chroma => by(&|i: usize| chroma(&lab[i])),
+        SortOrder::NearestNeighbor => nearest_neighbor_path(&lab),
+        SortOrder::Hsl => hsl_permutation(centroids),
     }
 }
+
+/// Order colors by HSL saturation, then hue, then lightness.
+fn hsl_permutation(centroids: &[[f32; 3]]) -> Vec<u8> {
+    let scale: f32 = rgb_scale(centroids);
+
+    // Sort the color indices by their HSL values
+    let mut hsl_colors: Vec<([f32; 3], u8)> = centroids
+        .par_iter()
+        .enumerate()
+        .map(|(i, color): (usize, &[f32; 3])| {
+            let rgb: [f32; 3] = color.map(|v: f32| v / scale);
+            let (max_value, min_value): (f32, f32) = rgb.iter().fold(
+                (f32::NEG_INFINITY, f32::INFINITY),
+                |(max, min): (f32, f32), &val: &f32| (max.max(val), min.min(val)),
+            );
+            let luminance: f32 = (max_value + min_value) / 2.0;
+            let saturation: f32 = match max_value == min_value {
+                true => 0.0,
+                false if luminance <= 0.5 => (max_value - min_value) / (max_value + min_value),
+                false => (max_value - min_value) / (2.0 - max_value - min_value),
+            };
+            let mut hue: f32 = if max_value == min_value {
+                0.0
+            } else if max_value == rgb[0] {
+                ((rgb[1] - rgb[2]) / (max_value - min_value)) * 60.0
+            } else if max_value == rgb[1] {
+                (2.0 + (rgb[2] - rgb[0]) / (max_value - min_value)) * 60.0
+            } else {
+                (4.0 + (rgb[0] - rgb[1]) / (max_value - min_value)) * 60.0
+            };
+
+            if hue.is_sign_negative() {
+                hue += 360.0;
+            }
+
+            ([hue, saturation, luminance], i as u8)
+        })
+        .collect();
+
+    hsl_colors.par_sort_by(|(a, _): &([f32; 3], u8), (b, _): &([f32; 3], u8)| {
+        a[1].partial_cmp(&b[1])
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| a[0].partial_cmp(&b[0]).unwrap_or(Ordering::Equal))
+            .then_with(|| a[2].partial_cmp(&b[2]).unwrap_or(Ordering::Equal))
+    });
+
+    hsl_colors
+        .into_iter()
+        .map(|(_, i): ([f32; 3], u8)| i)
+        .collect()
+}
+
+/// Chroma below which a color is treated as gray for hue ordering.
+const GRAY_CHROMA: f32 = 5.0;
+
+/// Returns `255.0` if the colors are in the range `0.0..=255.0`, or `1.0` if
+/// every channel is at most `1.0`.
+fn rgb_scale(colors: &[[f32; 3]]) -> f32 {
+    if colors
+        .par_iter()
+        .any(|rgb: &[f32; 3]| rgb.iter().any(|v: &f32| *v > 1.0))
+    {
+        255.0
+    } else {
+        1.0
+    }
+}
+
+/// Convert an sRGB color with channels in `0.0..=1.0` to CIELAB under the D65
+/// white point.
+pub(crate) fn srgb_to_lab(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b]: [f32; 3] = rgb.map(|c: f32| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    });
+
+    let xyz: [f32; 3] = [
+        (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047,
+        0.2126 * r + 0.7152 * g + 0.0722 * b,
+        (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883,
+    ];
+    let [fx, fy, fz]: [f32; 3] = xyz.map(|t: f32| {
+        const DELTA: f32 = 6.0 / 29.0;
+        if t > DELTA * DELTA * DELTA {
+            t.cbrt()
+        } else {
+            t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+        }
+    });
+
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// Order colors along a path starting from the darkest color, greedily
+/// stepping to the nearest remaining color and then shortening the path with
+/// 2-opt moves.
+fn nearest_neighbor_path(lab: &[[f32; 3]]) -> Vec<u8> {
+    let delta_e = |a: usize, b: usize| -> f32 {
+        lab[a]
+            .iter()
+            .zip(lab[b].iter())
+            .map(|(x, y): (&f32, &f32)| (x - y) * (x - y))
+            .sum::<f32>()
+            .sqrt()
+    };
+
+    let mut remaining: Vec<usize> = (0..lab.len()).collect();
+    let mut path: Vec<usize> = Vec::with_capacity(lab.len());
+    let start: Option<usize> = remaining.iter().copied().min_by(|&a: &usize, &b: &usize| {
+        lab[a][0].partial_cmp(&lab[b][0]).unwrap_or(Ordering::Equal)
+    });
+    if let Some(start) = start {
+        remaining.retain(|&i: &usize| i != start);
+        path.push(start);
+    }
+    while let Some(&last) = path.last() {
+        let next: Option<(usize, usize)> = remaining.iter().copied().enumerate().min_by(
+            |&(_, a): &(usize, usize), &(_, b): &(usize, usize)| {
+                delta_e(last, a)
+                    .partial_cmp(&delta_e(last, b))
+                    .unwrap_or(Ordering::Equal)
+            },
+        );
+        match next {
+            Some((pos, color)) => {
+                remaining.swap_remove(pos);
+                path.push(color);
+            }
+            None => break,
+        }
+    }
+
+    // Reverse segments of the open path while that shortens it
+    let n: usize = path.len();
+    let mut improved: bool = true;
+    while improved {
+        improved = false;
+        (1..n.saturating_sub(1)).for_each(|i: usize| {
+            (i + 1..n).for_each(|j: usize| {
+                let before: f32 = delta_e(path[i - 1], path[i])
+                    + if j + 1 < n {
+                        delta_e(path[j], path[j + 1])
+                    } else {
+                        0.0
+                    };
+                let after: f32 = delta_e(path[i - 1], path[j])
+                    + if j + 1 < n {
+                        delta_e(path[i], path[j + 1])
+                    } else {
+                        0.0
+                    };
+                if after + 1e-4 < before {
+                    path[i..=j].reverse();
+                    improved = true;
+                }
+            });
+        });
+    }
+
+    path.iter().map(|&i: &usize| i as u8).collect()
+}
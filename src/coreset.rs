@@ -0,0 +1,253 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::{rngs::SmallRng, thread_rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::kmeans::{closest_centroid, get_closest_centroid_with_metric, lloyd, Calculate, Kmeans};
+
+/// A weighted subset of a buffer whose k-means cost approximates the cost of
+/// the full buffer.
+#[derive(Clone, Debug, Default)]
+pub struct Coreset<C: Calculate> {
+    /// Points sampled from the buffer.
+    pub points: Vec<C>,
+    /// Weight of each sampled point, the weights sum to roughly the length of
+    /// the buffer.
+    pub weights: Vec<f32>,
+}
+
+impl<C: Calculate + Sync> Coreset<C> {
+    /// Weighted sum of the differences between each point and its nearest
+    /// centroid, an estimate of the k-means cost of the full buffer.
+    pub fn cost(&self, centroids: &[C]) -> f32 {
+        self.points
+            .par_iter()
+            .zip(self.weights.par_iter())
+            .map(|(point, &weight): (&C, &f32)| {
+                weight
+                    * closest_centroid(point, centroids, &|c1: &C, c2: &C| C::difference(c1, c2)).1
+            })
+            .sum()
+    }
+}
+
+/// Build a coreset of a buffer by sensitivity sampling.
+///
+/// `k` centroids are seeded with k-means++ and each point is sampled with
+/// probability proportional to its sensitivity, the share of its difference
+/// to the nearest seed in the total plus the inverse size of its seed's
+/// cluster. Sampled points are weighted by their inverse probability, so
+/// outliers are kept with small weights and dense regions are covered with
+/// few heavy points.
+///
+/// - `k` - number of clusters the coreset will be used for.
+/// - `size` - number of points to sample.
+/// - `buf` - array of points.
+///
+/// # Panics
+///
+/// Panics if buffer is empty.
+///
+/// ```
+/// use kmeans_colors::build_coreset;
+///
+/// let mut buf = vec![[0.0; 3]; 50];
+/// buf.extend([[200.0; 3]; 50]);
+/// let coreset = build_coreset(2, 20, &buf);
+///
+/// // The weights stand in for the whole buffer
+/// assert_eq!(coreset.points.len(), 20);
+/// assert!((coreset.weights.iter().sum::<f32>() - 100.0).abs() < 1e-3);
+/// assert_eq!(coreset.cost(&[[0.0; 3], [200.0; 3]]), 0.0);
+/// assert!((coreset.cost(&[[100.0; 3]]) / (100.0 * 30000.0) - 1.0).abs() < 1e-3);
+/// ```
+///
+/// ## Reference
+///
+/// Bachem, O., Lucic, M., & Krause, A. (2018). Scalable k-means clustering
+/// via lightweight coresets. In: Proceedings of the 24th ACM SIGKDD
+/// international conference on knowledge discovery & data mining.
+///
+/// Feldman, D., & Langberg, M. (2011). A unified framework for approximating
+/// and clustering data. In: Proceedings of the forty-third annual ACM
+/// symposium on Theory of computing.
+pub fn build_coreset<C: Calculate + Clone + Sync + Send>(
+    k: usize,
+    size: usize,
+    buf: &[C],
+) -> Coreset<C> {
+    assert!(!buf.is_empty());
+
+    let mut rng: SmallRng = SmallRng::from_rng(thread_rng()).unwrap();
+    let mut seeds: Vec<C> = Vec::with_capacity(k);
+    crate::plus_plus::init_plus_plus(k.max(1), &mut rng, buf, &mut seeds);
+
+    // Difference to the nearest seed and the size of each seed's cluster
+    let nearest: Vec<(usize, f32)> = buf
+        .par_iter()
+        .map(|point: &C| closest_centroid(point, &seeds, &|c1: &C, c2: &C| C::difference(c1, c2)))
+        .collect();
+    let mut sizes: Vec<f32> = vec![0.0; seeds.len()];
+    nearest
+        .iter()
+        .for_each(|&(i, _): &(usize, f32)| sizes[i] += 1.0);
+    let total: f32 = nearest.par_iter().map(|&(_, d): &(usize, f32)| d).sum();
+
+    let sensitivities: Vec<f32> = nearest
+        .par_iter()
+        .map(|&(i, d): &(usize, f32)| {
+            let share: f32 = if total.is_normal() { d / total } else { 0.0 };
+            share + 1.0 / sizes[i]
+        })
+        .collect();
+    let sum: f32 = sensitivities.par_iter().sum();
+
+    let sampler: WeightedIndex<f32> =
+        WeightedIndex::new(&sensitivities).expect("Failed to create weighted index.");
+    let (points, weights): (Vec<C>, Vec<f32>) = (0..size)
+        .map(|_| {
+            let i: usize = sampler.sample(&mut rng);
+            (buf[i].clone(), sum / (size as f32 * sensitivities[i]))
+        })
+        .unzip();
+
+    Coreset { points, weights }
+}
+
+/// Find the k-means centroids of a buffer from a coreset. Takes the same
+/// arguments as [`get_kmeans`](fn.get_kmeans.html) along with the coreset
+/// size.
+///
+/// A coreset of `size` points is built with
+/// [`build_coreset`](fn.build_coreset.html), weighted k-means is run on the
+/// coreset, and the full buffer is indexed to the resulting centroids in a
+/// single pass. The cost of the result is within a bounded factor of k-means
+/// on the full buffer, at a fraction of the time for large buffers.
+///
+/// # Panics
+///
+/// Panics if buffer is empty or `size` is zero.
+///
+/// ```
+/// use kmeans_colors::get_kmeans_coreset;
+///
+/// let mut buf = vec![[0.0; 3]; 50];
+/// buf.extend([[200.0; 3]; 50]);
+/// let mut result = get_kmeans_coreset(2, 20, 20, 0.0, false, &buf);
+///
+/// // The full buffer is indexed to centroids fit on the coreset
+/// assert_eq!(result.indices.len(), 100);
+/// assert_ne!(result.indices[0], result.indices[99]);
+/// result.centroids.sort_by(|a, b| a[0].total_cmp(&b[0]));
+/// assert_eq!(result.centroids[0], [0.0; 3]);
+/// assert!(result.centroids[1].iter().all(|v| (v - 200.0).abs() < 1e-3));
+/// ```
+pub fn get_kmeans_coreset<const N: usize>(
+    k: usize,
+    size: usize,
+    max_iter: usize,
+    converge: f32,
+    verbose: bool,
+    buf: &[[f32; N]],
+) -> Kmeans<[f32; N]> {
+    assert!(size > 0);
+    let coreset: Coreset<[f32; N]> = build_coreset(k, size, buf);
+
+    // Initialize the random centroids
+    let mut rng: SmallRng = SmallRng::from_rng(thread_rng()).unwrap();
+    let centroids: Vec<[f32; N]> = init_plus_plus_weighted(k, &mut rng, &coreset);
+
+    let metric = |c1: &[f32; N], c2: &[f32; N]| <[f32; N]>::difference(c1, c2);
+    let result: Kmeans<[f32; N]> = lloyd(
+        centroids,
+        max_iter,
+        converge,
+        verbose,
+        &coreset.points,
+        &metric,
+        |points: &[[f32; N]], centroids: &[[f32; N]], indices: &mut Vec<u8>| {
+            get_closest_centroid_with_metric(points, centroids, indices, &metric)
+        },
+        |points: &[[f32; N]], centroids: &mut [[f32; N]], indices: &[u8]| {
+            recalculate_weighted(&mut rng, points, &coreset.weights, centroids, indices)
+        },
+    );
+
+    let mut indices: Vec<u8> = Vec::with_capacity(buf.len());
+    get_closest_centroid_with_metric(buf, &result.centroids, &mut indices, &metric);
+
+    Kmeans {
+        score: result.score,
+        centroids: result.centroids,
+        indices,
+        locked: Vec::new(),
+    }
+}
+
+/// k-means++ initialization on a coreset, sampling each point with
+/// probability proportional to its weight times `D(x)^2`.
+fn init_plus_plus_weighted<C: Calculate + Clone + Sync>(
+    k: usize,
+    rng: &mut SmallRng,
+    coreset: &Coreset<C>,
+) -> Vec<C> {
+    let mut centroids: Vec<C> = Vec::with_capacity(k);
+    let mut distances: Vec<f32> = vec![f32::MAX; coreset.points.len()];
+    while centroids.len() < k {
+        let weights: Vec<f32> = if centroids.is_empty() {
+            coreset.weights.clone()
+        } else {
+            coreset
+                .weights
+                .iter()
+                .zip(distances.iter())
+                .map(|(w, d): (&f32, &f32)| w * d)
+                .collect()
+        };
+        let sampler: WeightedIndex<f32> = match WeightedIndex::new(&weights) {
+            Ok(sampler) => sampler,
+            // Centroids match all points
+            Err(_) => break,
+        };
+        let centroid: C = coreset.points[sampler.sample(rng)].clone();
+        distances
+            .par_iter_mut()
+            .zip(coreset.points.par_iter())
+            .for_each(|(d, point): (&mut f32, &C)| *d = d.min(C::difference(point, &centroid)));
+        centroids.push(centroid);
+    }
+    centroids
+}
+
+/// Find the new centroid locations based on the weighted average of the
+/// points that correspond to the centroid. If no points correspond, the
+/// centroid is re-initialized with a random point.
+fn recalculate_weighted<const N: usize>(
+    rng: &mut SmallRng,
+    points: &[[f32; N]],
+    weights: &[f32],
+    centroids: &mut [[f32; N]],
+    indices: &[u8],
+) {
+    let mut sums: Vec<([f32; N], f32)> = vec![([0.0; N], 0.0); centroids.len()];
+    points
+        .iter()
+        .zip(weights.iter())
+        .zip(indices.iter())
+        .for_each(|((point, &weight), &index): ((&[f32; N], &f32), &u8)| {
+            let (sum, total): &mut ([f32; N], f32) = &mut sums[index as usize];
+            sum.iter_mut()
+                .zip(point.iter())
+                .for_each(|(s, p): (&mut f32, &f32)| *s += weight * p);
+            *total += weight;
+        });
+
+    centroids.iter_mut().zip(sums.iter()).for_each(
+        |(centroid, (sum, total)): (&mut [f32; N], &([f32; N], f32))| {
+            *centroid = if *total > 0.0 {
+                sum.map(|v: f32| v / total)
+            } else {
+                <[f32; N]>::create_random(rng)
+            };
+        },
+    );
+}
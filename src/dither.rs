@@ -0,0 +1,354 @@
+use std::sync::OnceLock;
+
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+/// Error diffusion kernel, the distribution of a pixel's quantization error
+/// to its unprocessed neighbors.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Kernel {
+    /// Floyd-Steinberg, diffuses to four neighbors over two rows.
+    FloydSteinberg,
+    /// Jarvis-Judice-Ninke, diffuses to twelve neighbors over three rows.
+    JarvisJudiceNinke,
+    /// Stucki, a sharper variant of Jarvis-Judice-Ninke.
+    Stucki,
+    /// Atkinson, diffuses only three quarters of the error, preserving
+    /// contrast at the cost of detail in highlights and shadows.
+    Atkinson,
+    /// Sierra, diffuses to ten neighbors over three rows.
+    Sierra,
+}
+
+impl Kernel {
+    /// Returns the `(dx, dy, weight)` of each neighbor receiving error, with
+    /// `dx` relative to the scan direction.
+    pub fn weights(&self) -> &'static [(isize, usize, f32)] {
+        match self {
+            Kernel::FloydSteinberg => &[
+                (1, 0, 7.0 / 16.0),
+                (-1, 1, 3.0 / 16.0),
+                (0, 1, 5.0 / 16.0),
+                (1, 1, 1.0 / 16.0),
+            ],
+            Kernel::JarvisJudiceNinke => &[
+                (1, 0, 7.0 / 48.0),
+                (2, 0, 5.0 / 48.0),
+                (-2, 1, 3.0 / 48.0),
+                (-1, 1, 5.0 / 48.0),
+                (0, 1, 7.0 / 48.0),
+                (1, 1, 5.0 / 48.0),
+                (2, 1, 3.0 / 48.0),
+                (-2, 2, 1.0 / 48.0),
+                (-1, 2, 3.0 / 48.0),
+                (0, 2, 5.0 / 48.0),
+                (1, 2, 3.0 / 48.0),
+                (2, 2, 1.0 / 48.0),
+            ],
+            Kernel::Stucki => &[
+                (1, 0, 8.0 / 42.0),
+                (2, 0, 4.0 / 42.0),
+                (-2, 1, 2.0 / 42.0),
+                (-1, 1, 4.0 / 42.0),
+                (0, 1, 8.0 / 42.0),
+                (1, 1, 4.0 / 42.0),
+                (2, 1, 2.0 / 42.0),
+                (-2, 2, 1.0 / 42.0),
+                (-1, 2, 2.0 / 42.0),
+                (0, 2, 4.0 / 42.0),
+                (1, 2, 2.0 / 42.0),
+                (2, 2, 1.0 / 42.0),
+            ],
+            Kernel::Atkinson => &[
+                (1, 0, 1.0 / 8.0),
+                (2, 0, 1.0 / 8.0),
+                (-1, 1, 1.0 / 8.0),
+                (0, 1, 1.0 / 8.0),
+                (1, 1, 1.0 / 8.0),
+                (0, 2, 1.0 / 8.0),
+            ],
+            Kernel::Sierra => &[
+                (1, 0, 5.0 / 32.0),
+                (2, 0, 3.0 / 32.0),
+                (-2, 1, 2.0 / 32.0),
+                (-1, 1, 4.0 / 32.0),
+                (0, 1, 5.0 / 32.0),
+                (1, 1, 4.0 / 32.0),
+                (2, 1, 2.0 / 32.0),
+                (-1, 2, 2.0 / 32.0),
+                (0, 2, 3.0 / 32.0),
+                (1, 2, 2.0 / 32.0),
+            ],
+        }
+    }
+}
+
+/// Options for error diffusion dithering.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ErrorDiffusion {
+    /// Kernel distributing the quantization error.
+    pub kernel: Kernel,
+    /// Alternate the scan direction of each row, which avoids the diagonal
+    /// artifacts of scanning every row left to right.
+    pub serpentine: bool,
+    /// Share of the quantization error that is diffused, `1.0` for full
+    /// diffusion and `0.0` for plain nearest color mapping.
+    pub strength: f32,
+}
+
+impl ErrorDiffusion {
+    /// Create new `ErrorDiffusion` options with serpentine scanning and full
+    /// strength.
+    pub const fn new(kernel: Kernel) -> Self {
+        ErrorDiffusion {
+            kernel,
+            serpentine: true,
+            strength: 1.0,
+        }
+    }
+}
+
+impl Default for ErrorDiffusion {
+    fn default() -> Self {
+        ErrorDiffusion::new(Kernel::FloydSteinberg)
+    }
+}
+
+/// Threshold map for ordered dithering.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ThresholdMap {
+    /// 2x2 Bayer matrix.
+    Bayer2,
+    /// 4x4 Bayer matrix.
+    Bayer4,
+    /// 8x8 Bayer matrix.
+    Bayer8,
+    /// 64x64 blue noise generated by the void-and-cluster method, which has no
+    /// visible repeating pattern.
+    BlueNoise,
+}
+
+/// Side length of the blue noise threshold map.
+const BLUE_NOISE_SIZE: usize = 64;
+
+impl ThresholdMap {
+    /// Returns the side length of the map and its thresholds in row-major
+    /// order. Each threshold is distinct and in the range `0.0..1.0`.
+    ///
+    /// ```
+    /// use kmeans_colors::ThresholdMap;
+    ///
+    /// assert_eq!(ThresholdMap::Bayer2.thresholds(), (2, vec![0.125, 0.625, 0.875, 0.375]));
+    ///
+    /// // Blue noise ranks every cell once
+    /// let (size, thresholds) = ThresholdMap::BlueNoise.thresholds();
+    /// let mut sorted = thresholds.clone();
+    /// sorted.sort_by(|a, b| a.total_cmp(b));
+    /// let count = (size * size) as f32;
+    /// assert!(sorted.iter().enumerate().all(|(i, &t)| t == (i as f32 + 0.5) / count));
+    ///
+    /// // and spreads them evenly, every 8x8 block averages close to one half
+    /// (0..size / 8).for_each(|block_y| {
+    ///     (0..size / 8).for_each(|block_x| {
+    ///         let sum: f32 = (0..64)
+    ///             .map(|i| thresholds[(block_y * 8 + i / 8) * size + block_x * 8 + i % 8])
+    ///             .sum();
+    ///         assert!((sum / 64.0 - 0.5).abs() < 0.05);
+    ///     });
+    /// });
+    /// ```
+    pub fn thresholds(&self) -> (usize, Vec<f32>) {
+        let (size, ranks): (usize, Vec<usize>) = match self {
+            ThresholdMap::Bayer2 => (2, bayer(2)),
+            ThresholdMap::Bayer4 => (4, bayer(4)),
+            ThresholdMap::Bayer8 => (8, bayer(8)),
+            ThresholdMap::BlueNoise => {
+                static BLUE_NOISE: OnceLock<Vec<usize>> = OnceLock::new();
+                (
+                    BLUE_NOISE_SIZE,
+                    BLUE_NOISE
+                        .get_or_init(|| void_and_cluster(BLUE_NOISE_SIZE))
+                        .clone(),
+                )
+            }
+        };
+        let count: f32 = (size * size) as f32;
+        (
+            size,
+            ranks
+                .iter()
+                .map(|&r: &usize| (r as f32 + 0.5) / count)
+                .collect(),
+        )
+    }
+}
+
+/// Ranks of a Bayer matrix with a power of two side length, built
+/// recursively from the 2x2 matrix.
+fn bayer(size: usize) -> Vec<usize> {
+    let mut ranks: Vec<usize> = vec![0];
+    let mut n: usize = 1;
+    while n < size {
+        let mut next: Vec<usize> = vec![0; 4 * n * n];
+        (0..n).for_each(|y: usize| {
+            (0..n).for_each(|x: usize| {
+                let r: usize = 4 * ranks[y * n + x];
+                next[y * 2 * n + x] = r;
+                next[y * 2 * n + x + n] = r + 2;
+                next[(y + n) * 2 * n + x] = r + 3;
+                next[(y + n) * 2 * n + x + n] = r + 1;
+            });
+        });
+        ranks = next;
+        n *= 2;
+    }
+    ranks
+}
+
+/// Ranks of a blue noise matrix generated with the void-and-cluster method.
+///
+/// ## Reference
+///
+/// Ulichney, R. A. (1993). Void-and-cluster method for dither array
+/// generation. In: Human Vision, Visual Processing, and Digital Display IV.
+fn void_and_cluster(size: usize) -> Vec<usize> {
+    const SIGMA: f32 = 1.5;
+    let count: usize = size * size;
+
+    // Toroidal Gaussian filter indexed by offset
+    let filter: Vec<f32> = (0..count)
+        .map(|i: usize| {
+            let wrap = |d: usize| -> f32 { d.min(size - d) as f32 };
+            let (dx, dy): (f32, f32) = (wrap(i % size), wrap(i / size));
+            (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+    let offset = |a: usize, b: usize| -> usize {
+        let dx: usize = (a % size + size - b % size) % size;
+        let dy: usize = (a / size + size - b / size) % size;
+        dy * size + dx
+    };
+
+    let mut pattern: Vec<bool> = vec![false; count];
+    let mut energy: Vec<f32> = vec![0.0; count];
+    let toggle = |pattern: &mut Vec<bool>, energy: &mut Vec<f32>, p: usize, on: bool| {
+        pattern[p] = on;
+        let sign: f32 = if on { 1.0 } else { -1.0 };
+        energy
+            .iter_mut()
+            .enumerate()
+            .for_each(|(q, e): (usize, &mut f32)| *e += sign * filter[offset(q, p)]);
+    };
+    // Tightest cluster among the set pixels or largest void among the unset
+    let extreme = |pattern: &[bool], energy: &[f32], set: bool| -> usize {
+        let sign: f32 = if set { 1.0 } else { -1.0 };
+        (0..count)
+            .filter(|&p: &usize| pattern[p] == set)
+            .fold((0, f32::NEG_INFINITY), |best: (usize, f32), p: usize| {
+                if sign * energy[p] > best.1 {
+                    (p, sign * energy[p])
+                } else {
+                    best
+                }
+            })
+            .0
+    };
+
+    // Initial binary pattern, relaxed until it is evenly distributed
+    let mut rng: SmallRng = SmallRng::seed_from_u64(0);
+    let ones: usize = count / 10;
+    while pattern.iter().filter(|&&b: &&bool| b).count() < ones {
+        let p: usize = rng.gen_range(0..count);
+        if !pattern[p] {
+            toggle(&mut pattern, &mut energy, p, true);
+        }
+    }
+    loop {
+        let cluster: usize = extreme(&pattern, &energy, true);
+        toggle(&mut pattern, &mut energy, cluster, false);
+        let void: usize = extreme(&pattern, &energy, false);
+        toggle(&mut pattern, &mut energy, void, true);
+        if void == cluster {
+            break;
+        }
+    }
+
+    // Rank the initial pattern by removing tightest clusters, then fill the
+    // largest voids
+    let mut ranks: Vec<usize> = vec![0; count];
+    let (initial, initial_energy): (Vec<bool>, Vec<f32>) = (pattern.clone(), energy.clone());
+    (0..ones).rev().for_each(|rank: usize| {
+        let cluster: usize = extreme(&pattern, &energy, true);
+        toggle(&mut pattern, &mut energy, cluster, false);
+        ranks[cluster] = rank;
+    });
+    (pattern, energy) = (initial, initial_energy);
+    (ones..count).for_each(|rank: usize| {
+        let void: usize = extreme(&pattern, &energy, false);
+        toggle(&mut pattern, &mut energy, void, true);
+        ranks[void] = rank;
+    });
+
+    ranks
+}
+
+/// Options for ordered dithering.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OrderedDither {
+    /// Threshold map tiled over the image.
+    pub map: ThresholdMap,
+    /// Amplitude of the threshold offset added to each channel. `None` uses
+    /// the mean distance between each palette color and its nearest other
+    /// palette color.
+    pub spread: Option<f32>,
+}
+
+impl OrderedDither {
+    /// Create new `OrderedDither` options with the spread derived from the
+    /// palette.
+    pub const fn new(map: ThresholdMap) -> Self {
+        OrderedDither { map, spread: None }
+    }
+}
+
+impl Default for OrderedDither {
+    fn default() -> Self {
+        OrderedDither::new(ThresholdMap::Bayer8)
+    }
+}
+
+/// A trait for mapping an image buffer onto a palette with dithering.
+pub trait Dither: Sized + crate::Calculate {
+    /// Index each pixel of an image buffer, stored in rows of `width` pixels,
+    /// with a palette color by error diffusion. The indexed buffer can be
+    /// turned into colors with
+    /// [`MapColor::map_indices_to_centroids`][map].
+    ///
+    /// [map]: trait.MapColor.html#tymethod.map_indices_to_centroids
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is zero or the palette is empty.
+    fn dither_error_diffusion(
+        buf: &[Self],
+        width: usize,
+        palette: &[Self],
+        options: &ErrorDiffusion,
+    ) -> Vec<u8>;
+
+    /// Index each pixel of an image buffer, stored in rows of `width` pixels,
+    /// with a palette color by ordered dithering. Each pixel is offset by the
+    /// threshold map tiled over the image before finding its nearest palette
+    /// color, so pixels are processed in parallel and the output of a pixel
+    /// depends only on its color and position. This keeps the dither pattern
+    /// stable across animation frames.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is zero or the palette is empty.
+    fn dither_ordered(
+        buf: &[Self],
+        width: usize,
+        palette: &[Self],
+        options: &OrderedDither,
+    ) -> Vec<u8>;
+}
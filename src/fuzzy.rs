@@ -0,0 +1,251 @@
+use rand::{rngs::SmallRng, thread_rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::kmeans::Calculate;
+
+/// Struct result of fuzzy c-means calculation with convergence score,
+/// centroids, soft memberships, and hard indexed buffer.
+#[derive(Clone, Debug, Default)]
+pub struct FuzzyCmeans<C: Calculate> {
+    /// Sum of squares distance metric for centroids compared to old centroids.
+    pub score: f32,
+    /// Points determined to be centroids of input buffer.
+    pub centroids: Vec<C>,
+    /// Membership of each point to each centroid, stored in rows of
+    /// `centroids.len()` values per point. Each row sums to `1.0`.
+    pub memberships: Vec<f32>,
+    /// Buffer of points indexed to the centroid with the highest membership.
+    pub indices: Vec<u8>,
+}
+
+impl<C: Calculate> FuzzyCmeans<C> {
+    /// Create a new `FuzzyCmeans` struct to contain fuzzy c-means results.
+    pub fn new() -> Self {
+        FuzzyCmeans {
+            score: f32::MAX,
+            centroids: Vec::new(),
+            memberships: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    /// Returns the memberships of the point at `index` to each centroid.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn membership(&self, index: usize) -> &[f32] {
+        let k: usize = self.centroids.len();
+        &self.memberships[index * k..(index + 1) * k]
+    }
+}
+
+impl<const N: usize> FuzzyCmeans<[f32; N]> {
+    /// Blend a palette by the memberships of each point, producing an output
+    /// buffer for soft recoloring. `palette` replaces the centroids by index
+    /// and must have the same length as the centroids.
+    pub fn blend(&self, palette: &[[f32; N]]) -> Vec<[f32; N]> {
+        let k: usize = self.centroids.len();
+        self.memberships
+            .par_chunks_exact(k.max(1))
+            .map(|row: &[f32]| {
+                row.iter().zip(palette.iter()).fold(
+                    [0.0; N],
+                    |mut acc: [f32; N], (u, color): (&f32, &[f32; N])| {
+                        acc.iter_mut()
+                            .zip(color.iter())
+                            .for_each(|(a, c): (&mut f32, &f32)| *a += u * c);
+                        acc
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+/// Calculate the memberships of a point to each centroid.
+fn update_memberships<const N: usize>(
+    point: &[f32; N],
+    centroids: &[[f32; N]],
+    exponent: f32,
+    row: &mut [f32],
+) {
+    row.iter_mut()
+        .zip(centroids.iter())
+        .for_each(|(u, c): (&mut f32, &[f32; N])| *u = <[f32; N]>::difference(point, c));
+
+    // A point lying on centroids belongs fully to them
+    let zeros: usize = row.iter().filter(|d: &&f32| **d == 0.0).count();
+    if zeros > 0 {
+        row.iter_mut()
+            .for_each(|u: &mut f32| *u = if *u == 0.0 { 1.0 / zeros as f32 } else { 0.0 });
+        return;
+    }
+
+    // `u_ij = 1 / sum_l (d_ij / d_il)^(2 / (m - 1))` on squared differences,
+    // scaled by the smallest difference to avoid overflow
+    let min: f32 = row.iter().fold(f32::MAX, |min: f32, &d: &f32| min.min(d));
+    row.iter_mut()
+        .for_each(|u: &mut f32| *u = (min / *u).powf(exponent));
+    let sum: f32 = row.iter().sum();
+    row.iter_mut().for_each(|u: &mut f32| *u /= sum);
+}
+
+/// Find the fuzzy c-means centroids and memberships of a buffer.
+///
+/// Each point belongs to every centroid with a membership between `0.0` and
+/// `1.0`, and the centroids are the means of all points weighted by their
+/// memberships raised to `fuzziness`. A `fuzziness` close to `1.0` approaches
+/// hard k-means, while larger values produce softer memberships; `2.0` is a
+/// common choice. The `indices` of the result hold the centroid with the
+/// highest membership of each point.
+///
+/// - `k` - number of clusters.
+/// - `fuzziness` - fuzziness exponent `m`, greater than `1.0`.
+/// - `max_iter` - maximum number of iterations.
+/// - `converge` - threshold for convergence.
+/// - `verbose` - flag for printing convergence information to console.
+/// - `buf` - array of points.
+///
+/// # Panics
+///
+/// Panics if `fuzziness` is not greater than `1.0` or if buffer is empty.
+///
+/// ```
+/// use kmeans_colors::get_fuzzy_cmeans;
+///
+/// let buf = [[0.0; 3], [1.0; 3], [2.0; 3], [253.0; 3], [254.0; 3], [255.0; 3]];
+/// let result = get_fuzzy_cmeans(2, 2.0, 100, 1e-6, false, &buf);
+///
+/// (0..buf.len()).for_each(|i| {
+///     let row = result.membership(i);
+///     assert!((row.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+///
+///     // Points belong almost fully to their own group, which is their index
+///     let own = result.indices[i] as usize;
+///     assert!(row[own] > 0.999);
+///     assert!(row.iter().all(|&u| u <= row[own]));
+/// });
+/// assert_eq!(result.indices[..3], [result.indices[0]; 3]);
+/// assert_eq!(result.indices[3..], [result.indices[3]; 3]);
+/// assert_ne!(result.indices[0], result.indices[3]);
+///
+/// // Blending with near-hard memberships recolors with the palette
+/// let mut palette = [[255.0, 0.0, 0.0]; 2];
+/// palette[result.indices[3] as usize] = [0.0, 0.0, 255.0];
+/// let blended = result.blend(&palette);
+/// blended.iter().zip(result.indices.iter()).for_each(|(color, &i)| {
+///     let target = palette[i as usize];
+///     assert!(color.iter().zip(target.iter()).all(|(a, b)| (a - b).abs() < 0.5));
+/// });
+/// ```
+///
+/// ## Reference
+///
+/// Bezdek, J. C., Ehrlich, R., & Full, W. (1984). FCM: The fuzzy c-means
+/// clustering algorithm. Computers & geosciences, 10(2-3), 191-203.
+pub fn get_fuzzy_cmeans<const N: usize>(
+    k: usize,
+    fuzziness: f32,
+    max_iter: usize,
+    converge: f32,
+    verbose: bool,
+    buf: &[[f32; N]],
+) -> FuzzyCmeans<[f32; N]> {
+    assert!(fuzziness > 1.0);
+    if k == 0 {
+        return FuzzyCmeans::new();
+    }
+
+    // Initialize the random centroids
+    let mut rng: SmallRng = SmallRng::from_rng(thread_rng()).unwrap();
+    let mut centroids: Vec<[f32; N]> = Vec::with_capacity(k);
+    crate::plus_plus::init_plus_plus(k, &mut rng, buf, &mut centroids);
+    let k: usize = centroids.len();
+
+    let exponent: f32 = 1.0 / (fuzziness - 1.0);
+    let mut iterations: usize = 0;
+    let mut score: f32;
+    let mut old_centroids: Vec<[f32; N]> = centroids.clone();
+    let mut memberships: Vec<f32> = vec![0.0; buf.len() * k];
+
+    // Main loop: update memberships and recalculate weighted means until
+    // convergence
+    loop {
+        memberships
+            .par_chunks_exact_mut(k)
+            .zip(buf.par_iter())
+            .for_each(|(row, point): (&mut [f32], &[f32; N])| {
+                update_memberships(point, &centroids, exponent, row)
+            });
+
+        centroids
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(j, centroid): (usize, &mut [f32; N])| {
+                let (sum, total): ([f32; N], f32) =
+                    memberships.chunks_exact(k).zip(buf.iter()).fold(
+                        ([0.0; N], 0.0),
+                        |(mut sum, total): ([f32; N], f32), (row, point): (&[f32], &[f32; N])| {
+                            let weight: f32 = row[j].powf(fuzziness);
+                            sum.iter_mut()
+                                .zip(point.iter())
+                                .for_each(|(s, p): (&mut f32, &f32)| *s += weight * p);
+                            (sum, total + weight)
+                        },
+                    );
+                if total > 0.0 {
+                    *centroid = sum.map(|v: f32| v / total);
+                }
+            });
+
+        score = <[f32; N]>::check_loop(&centroids, &old_centroids);
+        if verbose {
+            println!("Score: {}", score);
+        }
+
+        if iterations >= max_iter || score <= converge {
+            if verbose {
+                println!("Iterations: {}", iterations);
+            }
+            break;
+        }
+
+        iterations += 1;
+        old_centroids.clone_from(&centroids);
+    }
+
+    // Memberships of the returned centroids
+    memberships
+        .par_chunks_exact_mut(k)
+        .zip(buf.par_iter())
+        .for_each(|(row, point): (&mut [f32], &[f32; N])| {
+            update_memberships(point, &centroids, exponent, row)
+        });
+
+    let indices: Vec<u8> = memberships
+        .par_chunks_exact(k)
+        .map(|row: &[f32]| {
+            row.iter()
+                .enumerate()
+                .fold(
+                    (0, f32::MIN),
+                    |(idx, max): (usize, f32), (j, &u): (usize, &f32)| {
+                        if u > max {
+                            (j, u)
+                        } else {
+                            (idx, max)
+                        }
+                    },
+                )
+                .0 as u8
+        })
+        .collect();
+
+    FuzzyCmeans {
+        score,
+        centroids,
+        memberships,
+        indices,
+    }
+}
@@ -0,0 +1,389 @@
+use rand::{rngs::SmallRng, thread_rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::kmeans::{get_closest_centroid_with_metric, get_kmeans, Kmeans};
+use crate::metric::SquaredEuclidean;
+
+/// Regularization added to the covariance diagonals to keep them invertible.
+const REGULARIZATION: f64 = 1e-6;
+
+/// Shape of the covariance matrices of a Gaussian mixture model.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Covariance {
+    /// Each component has a diagonal covariance matrix, the channels are
+    /// independent within a component.
+    Diagonal,
+    /// Each component has a full covariance matrix, allowing elongated
+    /// components that are not aligned with the channel axes.
+    Full,
+}
+
+/// Initialization strategy for the means of a Gaussian mixture model.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GmmInit {
+    /// Means are chosen with k-means++.
+    PlusPlus,
+    /// Means are the centroids of a [`get_kmeans`](fn.get_kmeans.html) run.
+    Kmeans,
+}
+
+/// Struct result of fitting a Gaussian mixture model with expectation
+/// maximization.
+#[derive(Clone, Debug)]
+pub struct Gmm<const N: usize> {
+    /// Log-likelihood of the buffer under the fitted model.
+    pub log_likelihood: f32,
+    /// Means of each component.
+    pub means: Vec<[f32; N]>,
+    /// Covariance matrix of each component. Off-diagonal entries are zero for
+    /// [`Covariance::Diagonal`](enum.Covariance.html#variant.Diagonal).
+    pub covariances: Vec<[[f32; N]; N]>,
+    /// Mixing weight of each component, summing to `1.0`.
+    pub weights: Vec<f32>,
+    /// Responsibility of each component for each point, stored in rows of
+    /// `means.len()` values per point. Each row sums to `1.0`.
+    pub responsibilities: Vec<f32>,
+    /// Buffer of points indexed to the component with the highest
+    /// responsibility.
+    pub indices: Vec<u8>,
+    /// Shape of the covariance matrices.
+    pub covariance: Covariance,
+}
+
+impl<const N: usize> Gmm<N> {
+    /// Returns the responsibilities of each component for the point at
+    /// `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn responsibility(&self, index: usize) -> &[f32] {
+        let k: usize = self.means.len();
+        &self.responsibilities[index * k..(index + 1) * k]
+    }
+
+    /// Number of free parameters of the model.
+    pub fn parameters(&self) -> usize {
+        let k: usize = self.means.len();
+        let covariance: usize = match self.covariance {
+            Covariance::Diagonal => N,
+            Covariance::Full => N * (N + 1) / 2,
+        };
+        k.saturating_sub(1) + k * N + k * covariance
+    }
+
+    /// Bayesian information criterion of the model, lower is better.
+    pub fn bic(&self) -> f32 {
+        self.parameters() as f32 * (self.indices.len() as f32).ln() - 2.0 * self.log_likelihood
+    }
+
+    /// Akaike information criterion of the model, lower is better.
+    pub fn aic(&self) -> f32 {
+        2.0 * self.parameters() as f32 - 2.0 * self.log_likelihood
+    }
+}
+
+/// A Gaussian component with its covariance factored for density evaluation.
+#[derive(Copy, Clone)]
+struct Component<const N: usize> {
+    log_weight: f64,
+    mean: [f64; N],
+    /// Lower triangular Cholesky factor of the covariance matrix.
+    cholesky: [[f64; N]; N],
+    /// `N ln(2π) + ln|Σ|`
+    log_norm: f64,
+}
+
+impl<const N: usize> Component<N> {
+    fn new(weight: f64, mean: [f64; N], mut covariance: [[f64; N]; N]) -> Self {
+        (0..N).for_each(|i: usize| covariance[i][i] += REGULARIZATION);
+        let cholesky: [[f64; N]; N] = cholesky(&covariance);
+        let log_det: f64 = (0..N).map(|i: usize| 2.0 * cholesky[i][i].ln()).sum();
+        Component {
+            log_weight: weight.ln(),
+            mean,
+            cholesky,
+            log_norm: N as f64 * (2.0 * core::f64::consts::PI).ln() + log_det,
+        }
+    }
+
+    /// Weighted log density of a point.
+    fn log_density(&self, point: &[f32; N]) -> f64 {
+        // Solve `L y = x - μ` so the Mahalanobis distance is `|y|^2`
+        let mut y: [f64; N] = [0.0; N];
+        (0..N).for_each(|i: usize| {
+            let sum: f64 = (0..i).map(|j: usize| self.cholesky[i][j] * y[j]).sum();
+            y[i] = (point[i] as f64 - self.mean[i] - sum) / self.cholesky[i][i];
+        });
+        let mahalanobis: f64 = y.iter().map(|v: &f64| v * v).sum();
+        self.log_weight - 0.5 * (self.log_norm + mahalanobis)
+    }
+}
+
+/// Cholesky decomposition of a symmetric positive definite matrix.
+fn cholesky<const N: usize>(matrix: &[[f64; N]; N]) -> [[f64; N]; N] {
+    let mut lower: [[f64; N]; N] = [[0.0; N]; N];
+    (0..N).for_each(|i: usize| {
+        (0..=i).for_each(|j: usize| {
+            let sum: f64 = (0..j).map(|l: usize| lower[i][l] * lower[j][l]).sum();
+            if i == j {
+                lower[i][j] = (matrix[i][i] - sum).max(REGULARIZATION).sqrt();
+            } else {
+                lower[i][j] = (matrix[i][j] - sum) / lower[j][j];
+            }
+        });
+    });
+    lower
+}
+
+/// Estimate the weight, mean, and covariance of each component from the
+/// responsibilities.
+fn maximization<const N: usize>(
+    buf: &[[f32; N]],
+    responsibilities: &[f32],
+    k: usize,
+    covariance: Covariance,
+) -> Vec<Component<N>> {
+    (0..k)
+        .into_par_iter()
+        .map(|j: usize| {
+            let rows = || responsibilities.chunks_exact(k).zip(buf.iter());
+
+            let (sum, total): ([f64; N], f64) = rows().fold(
+                ([0.0; N], 0.0),
+                |(mut sum, total): ([f64; N], f64), (row, point): (&[f32], &[f32; N])| {
+                    let r: f64 = row[j] as f64;
+                    sum.iter_mut()
+                        .zip(point.iter())
+                        .for_each(|(s, p): (&mut f64, &f32)| *s += r * *p as f64);
+                    (sum, total + r)
+                },
+            );
+            let total: f64 = total.max(f64::MIN_POSITIVE);
+            let mean: [f64; N] = sum.map(|v: f64| v / total);
+
+            let mut cov: [[f64; N]; N] = rows().fold(
+                [[0.0; N]; N],
+                |mut cov: [[f64; N]; N], (row, point): (&[f32], &[f32; N])| {
+                    let r: f64 = row[j] as f64;
+                    (0..N).for_each(|a: usize| {
+                        let da: f64 = point[a] as f64 - mean[a];
+                        match covariance {
+                            Covariance::Diagonal => cov[a][a] += r * da * da,
+                            Covariance::Full => (0..=a).for_each(|b: usize| {
+                                cov[a][b] += r * da * (point[b] as f64 - mean[b]);
+                            }),
+                        }
+                    });
+                    cov
+                },
+            );
+            (0..N).for_each(|a: usize| {
+                (0..=a).for_each(|b: usize| {
+                    cov[a][b] /= total;
+                    cov[b][a] = cov[a][b];
+                });
+            });
+
+            Component::new(total / buf.len() as f64, mean, cov)
+        })
+        .collect()
+}
+
+/// Calculate the responsibilities of each component for each point, returns
+/// the log-likelihood of the buffer.
+fn expectation<const N: usize>(
+    buf: &[[f32; N]],
+    components: &[Component<N>],
+    responsibilities: &mut [f32],
+) -> f64 {
+    let k: usize = components.len();
+    responsibilities
+        .par_chunks_exact_mut(k)
+        .zip(buf.par_iter())
+        .map(|(row, point): (&mut [f32], &[f32; N])| {
+            let mut log_densities: Vec<f64> = components
+                .iter()
+                .map(|c: &Component<N>| c.log_density(point))
+                .collect();
+
+            // Log-sum-exp for the point's log-likelihood
+            let max: f64 = log_densities
+                .iter()
+                .fold(f64::NEG_INFINITY, |max: f64, &v: &f64| max.max(v));
+            log_densities
+                .iter_mut()
+                .for_each(|v: &mut f64| *v = (*v - max).exp());
+            let sum: f64 = log_densities.iter().sum();
+            row.iter_mut()
+                .zip(log_densities.iter())
+                .for_each(|(r, v): (&mut f32, &f64)| *r = (v / sum) as f32);
+
+            max + sum.ln()
+        })
+        .sum()
+}
+
+/// Fit a Gaussian mixture model to a buffer with expectation maximization.
+///
+/// Unlike k-means, each component has its own covariance, so elongated or
+/// differently sized clusters can be represented. The means are initialized
+/// according to `init` and the remaining parameters are estimated from the
+/// hard assignment of each point to its nearest mean. The calculation is
+/// complete when the log-likelihood improves by no more than `converge` or the
+/// number of iterations reaches `max_iter`.
+///
+/// - `k` - number of components.
+/// - `covariance` - shape of the covariance matrices.
+/// - `init` - initialization strategy for the means.
+/// - `max_iter` - maximum number of iterations.
+/// - `converge` - threshold for convergence of the log-likelihood.
+/// - `verbose` - flag for printing convergence information to console.
+/// - `buf` - array of points.
+///
+/// Use [`Gmm::bic`](struct.Gmm.html#method.bic) or
+/// [`Gmm::aic`](struct.Gmm.html#method.aic) to compare models with different
+/// `k` or covariance shapes.
+///
+/// # Panics
+///
+/// Panics if `k` is zero or if buffer is empty.
+///
+/// ```
+/// use kmeans_colors::{get_gmm, Covariance, GmmInit};
+///
+/// // Two discs of 30 points centered at 0.0 and 10.0
+/// let buf: Vec<[f32; 2]> = (0..60)
+///     .map(|i| {
+///         let (angle, radius) = (i as f32 * 2.4, ((i / 2) as f32 / 30.0).sqrt());
+///         let center = (i % 2) as f32 * 10.0;
+///         [center + radius * angle.cos(), center + radius * angle.sin()]
+///     })
+///     .collect();
+///
+/// // The BIC is lowest for the true number of components
+/// let bic: Vec<f32> = (1..=4)
+///     .map(|k| get_gmm(k, Covariance::Full, GmmInit::Kmeans, 100, 1e-4, false, &buf).bic())
+///     .collect();
+/// assert!(bic.iter().all(|&b| b >= bic[1]));
+///
+/// // The components are centered on the discs with equal weights
+/// let mut gmm = get_gmm(2, Covariance::Full, GmmInit::Kmeans, 100, 1e-4, false, &buf);
+/// gmm.means.sort_by(|a, b| a[0].total_cmp(&b[0]));
+/// assert!(gmm.means[0].iter().all(|v| v.abs() < 0.1));
+/// assert!(gmm.means[1].iter().all(|v| (v - 10.0).abs() < 0.1));
+/// assert!(gmm.weights.iter().all(|w| (w - 0.5).abs() < 1e-3));
+/// ```
+///
+/// ## Reference
+///
+/// Dempster, A. P., Laird, N. M., & Rubin, D. B. (1977). Maximum likelihood
+/// from incomplete data via the EM algorithm. Journal of the Royal Statistical
+/// Society: Series B, 39(1), 1-22.
+pub fn get_gmm<const N: usize>(
+    k: usize,
+    covariance: Covariance,
+    init: GmmInit,
+    max_iter: usize,
+    converge: f32,
+    verbose: bool,
+    buf: &[[f32; N]],
+) -> Gmm<N> {
+    assert!(k > 0 && !buf.is_empty());
+
+    // Initialize the means
+    let means: Vec<[f32; N]> = match init {
+        GmmInit::PlusPlus => {
+            let mut rng: SmallRng = SmallRng::from_rng(thread_rng()).unwrap();
+            let mut centroids: Vec<[f32; N]> = Vec::with_capacity(k);
+            crate::plus_plus::init_plus_plus(k, &mut rng, buf, &mut centroids);
+            centroids
+        }
+        GmmInit::Kmeans => {
+            let result: Kmeans<[f32; N]> = get_kmeans(k, max_iter, 0.0, false, buf);
+            result.centroids
+        }
+    };
+    let k: usize = means.len();
+
+    // Estimate the initial components from the hard assignment to the means
+    let mut indices: Vec<u8> = Vec::with_capacity(buf.len());
+    get_closest_centroid_with_metric(buf, &means, &mut indices, &SquaredEuclidean);
+    let mut responsibilities: Vec<f32> = vec![0.0; buf.len() * k];
+    responsibilities
+        .par_chunks_exact_mut(k)
+        .zip(indices.par_iter())
+        .for_each(|(row, &index): (&mut [f32], &u8)| row[index as usize] = 1.0);
+    let mut components: Vec<Component<N>> = maximization(buf, &responsibilities, k, covariance);
+
+    let mut iterations: usize = 0;
+    let mut log_likelihood: f64 = f64::NEG_INFINITY;
+
+    // Main loop: alternate expectation and maximization until convergence
+    loop {
+        let new_log_likelihood: f64 = expectation(buf, &components, &mut responsibilities);
+        let improvement: f64 = new_log_likelihood - log_likelihood;
+        log_likelihood = new_log_likelihood;
+        if verbose {
+            println!("Log-likelihood: {}", log_likelihood);
+        }
+
+        if iterations >= max_iter || improvement <= converge as f64 {
+            if verbose {
+                println!("Iterations: {}", iterations);
+            }
+            break;
+        }
+
+        components = maximization(buf, &responsibilities, k, covariance);
+        iterations += 1;
+    }
+
+    let indices: Vec<u8> = responsibilities
+        .par_chunks_exact(k)
+        .map(|row: &[f32]| {
+            row.iter()
+                .enumerate()
+                .fold(
+                    (0, f32::MIN),
+                    |(idx, max): (usize, f32), (j, &r): (usize, &f32)| {
+                        if r > max {
+                            (j, r)
+                        } else {
+                            (idx, max)
+                        }
+                    },
+                )
+                .0 as u8
+        })
+        .collect();
+
+    Gmm {
+        log_likelihood: log_likelihood as f32,
+        means: components
+            .iter()
+            .map(|c: &Component<N>| c.mean.map(|v: f64| v as f32))
+            .collect(),
+        covariances: components
+            .iter()
+            .map(|c: &Component<N>| {
+                let mut cov: [[f32; N]; N] = [[0.0; N]; N];
+                (0..N).for_each(|a: usize| {
+                    (0..N).for_each(|b: usize| {
+                        cov[a][b] = (0..N)
+                            .map(|l: usize| c.cholesky[a][l] * c.cholesky[b][l])
+                            .sum::<f64>() as f32;
+                    });
+                });
+                cov
+            })
+            .collect(),
+        weights: components
+            .iter()
+            .map(|c: &Component<N>| c.log_weight.exp() as f32)
+            .collect(),
+        responsibilities,
+        indices,
+        covariance,
+    }
+}
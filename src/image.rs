@@ -0,0 +1,315 @@
+use rayon::prelude::*;
+
+use crate::colors::{error_diffusion, MapColor};
+use crate::dither::{Dither, ErrorDiffusion, OrderedDither};
+use crate::kmeans::{get_closest_centroid_with_metric, Calculate, Kmeans};
+use crate::sort::CentroidData;
+
+/// A view of an 8-bit RGB or RGBA image buffer with its geometry.
+///
+/// Rows are `stride` bytes apart, which may be larger than
+/// `width * channels` for padded buffers. Pixels are converted to `[f32; 3]`
+/// colors in the range `0.0..=255.0` and ordered row by row, so indexed
+/// buffers produced from the view have `width * height` entries.
+///
+/// With an [alpha threshold](#method.with_alpha_threshold), RGBA pixels whose
+/// alpha is below the threshold are transparent. They are left out of
+/// [`pixels`](#method.pixels) and every indexed buffer, so clustering and
+/// percentages only consider the opaque pixels, and
+/// [`remap`](#method.remap) copies them through unchanged.
+///
+/// ```
+/// use kmeans_colors::{get_kmeans, ImageView};
+///
+/// // A 2x1 RGBA image of one black and one white pixel, with a padded row
+/// let data = [0, 0, 0, 255, 255, 255, 255, 255, 0, 0];
+/// let img = ImageView::with_stride(&data, 2, 1, 10, 4);
+///
+/// let result = get_kmeans(2, 20, 0.0, false, &img.pixels());
+/// let output = img.remap(&result.centroids, &result.indices);
+/// assert_eq!(output, [0, 0, 0, 255, 255, 255, 255, 255]);
+/// assert_eq!(img.inertia(&result), 0.0);
+///
+/// // A transparent pixel is left out of clustering and copied through
+/// let data = [10, 10, 10, 255, 200, 0, 0, 0, 30, 30, 30, 255];
+/// let img = ImageView::new(&data, 3, 1, 4).with_alpha_threshold(128);
+/// assert_eq!(img.pixels(), [[10.0; 3], [30.0; 3]]);
+///
+/// let result = get_kmeans(1, 20, 0.0, false, &img.pixels());
+/// let output = img.remap(&result.centroids, &result.indices);
+/// assert_eq!(output, [20, 20, 20, 255, 200, 0, 0, 0, 20, 20, 20, 255]);
+///
+/// // Statistics are read from the view and only count the opaque pixels
+/// assert_eq!(img.centroid_data(&result)[0].percentage, 1.0);
+/// assert_eq!(img.inertia(&result), 600.0);
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct ImageView<'a> {
+    data: &'a [u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+    channels: usize,
+    alpha_threshold: u8,
+}
+
+impl<'a> ImageView<'a> {
+    /// Create a new `ImageView` of a tightly packed buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channels` is not `3` or `4` or if the buffer is too short.
+    pub fn new(data: &'a [u8], width: usize, height: usize, channels: usize) -> Self {
+        Self::with_stride(data, width, height, width * channels, channels)
+    }
+
+    /// Create a new `ImageView` of a buffer whose rows are `stride` bytes
+    /// apart.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channels` is not `3` or `4`, if `stride` is less than
+    /// `width * channels`, or if the buffer is too short.
+    pub fn with_stride(
+        data: &'a [u8],
+        width: usize,
+        height: usize,
+        stride: usize,
+        channels: usize,
+    ) -> Self {
+        assert!(channels == 3 || channels == 4);
+        assert!(stride >= width * channels);
+        assert!(height == 0 || data.len() >= stride * (height - 1) + width * channels);
+
+        ImageView {
+            data,
+            width,
+            height,
+            stride,
+            channels,
+            alpha_threshold: 0,
+        }
+    }
+
+    /// Treat RGBA pixels with an alpha below `threshold` as transparent. A
+    /// threshold of `0` keeps every pixel and RGB images have no transparent
+    /// pixels.
+    pub fn with_alpha_threshold(mut self, threshold: u8) -> Self {
+        self.alpha_threshold = threshold;
+        self
+    }
+
+    /// Width of the image in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Height of the image in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Number of bytes between the starts of consecutive rows.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Number of channels of each pixel, `3` for RGB or `4` for RGBA.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Alpha below which pixels are transparent.
+    pub fn alpha_threshold(&self) -> u8 {
+        self.alpha_threshold
+    }
+
+    /// Returns `true` if a pixel is not transparent.
+    #[inline]
+    fn is_opaque(&self, pixel: &[u8]) -> bool {
+        !matches!(pixel.get(3), Some(&alpha) if alpha < self.alpha_threshold)
+    }
+
+    /// Returns the rows of the image without padding.
+    fn rows(&self) -> impl IndexedParallelIterator<Item = &'a [u8]> + '_ {
+        let row_len: usize = self.width * self.channels;
+        (0..self.height)
+            .into_par_iter()
+            .map(move |y: usize| &self.data[y * self.stride..y * self.stride + row_len])
+    }
+
+    /// Convert the opaque pixels to RGB colors in the range `0.0..=255.0`, row
+    /// by row.
+    pub fn pixels(&self) -> Vec<[f32; 3]> {
+        let channels: usize = self.channels;
+        self.rows()
+            .flat_map_iter(|row: &[u8]| {
+                row.chunks_exact(channels)
+                    .filter(|p: &&[u8]| self.is_opaque(p))
+                    .map(|p: &[u8]| [p[0] as f32, p[1] as f32, p[2] as f32])
+            })
+            .collect()
+    }
+
+    /// Number of opaque pixels.
+    pub fn opaque_count(&self) -> usize {
+        let channels: usize = self.channels;
+        self.rows()
+            .map(|row: &[u8]| {
+                row.chunks_exact(channels)
+                    .filter(|p: &&[u8]| self.is_opaque(p))
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Convert every pixel to an RGB color along with a mask of the opaque
+    /// pixels.
+    fn grid(&self) -> (Vec<[f32; 3]>, Vec<bool>) {
+        let channels: usize = self.channels;
+        self.rows()
+            .flat_map_iter(|row: &[u8]| {
+                row.chunks_exact(channels)
+                    .map(|p: &[u8]| ([p[0] as f32, p[1] as f32, p[2] as f32], self.is_opaque(p)))
+            })
+            .unzip()
+    }
+
+    /// Keep the indices of the opaque pixels.
+    fn opaque_indices(indices: Vec<u8>, mask: &[bool]) -> Vec<u8> {
+        indices
+            .into_iter()
+            .zip(mask.iter())
+            .filter(|&(_, &opaque): &(u8, &bool)| opaque)
+            .map(|(i, _): (u8, &bool)| i)
+            .collect()
+    }
+
+    /// Index each opaque pixel with its nearest palette color.
+    pub fn label(&self, palette: &[[f32; 3]]) -> Vec<u8> {
+        let mut indices: Vec<u8> = Vec::with_capacity(self.width * self.height);
+        get_closest_centroid_with_metric(
+            &self.pixels(),
+            palette,
+            &mut indices,
+            &|c1: &[f32; 3], c2: &[f32; 3]| <[f32; 3]>::difference(c1, c2),
+        );
+        indices
+    }
+
+    /// Index each opaque pixel with a palette color by error diffusion
+    /// dithering. Transparent pixels do not diffuse error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the palette is empty.
+    pub fn dither_error_diffusion(
+        &self,
+        palette: &[[f32; 3]],
+        options: &ErrorDiffusion,
+    ) -> Vec<u8> {
+        let (colors, mask): (Vec<[f32; 3]>, Vec<bool>) = self.grid();
+        let indices: Vec<u8> =
+            error_diffusion(&colors, self.width.max(1), palette, options, Some(&mask));
+        Self::opaque_indices(indices, &mask)
+    }
+
+    /// Index each opaque pixel with a palette color by ordered dithering.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the palette is empty.
+    pub fn dither_ordered(&self, palette: &[[f32; 3]], options: &OrderedDither) -> Vec<u8> {
+        let (colors, mask): (Vec<[f32; 3]>, Vec<bool>) = self.grid();
+        let indices: Vec<u8> =
+            <[f32; 3]>::dither_ordered(&colors, self.width.max(1), palette, options);
+        Self::opaque_indices(indices, &mask)
+    }
+
+    /// Offset of each row into the indexed buffer of the opaque pixels.
+    fn row_offsets(&self) -> Vec<usize> {
+        let channels: usize = self.channels;
+        let counts: Vec<usize> = self
+            .rows()
+            .map(|row: &[u8]| {
+                row.chunks_exact(channels)
+                    .filter(|p: &&[u8]| self.is_opaque(p))
+                    .count()
+            })
+            .collect();
+        counts
+            .iter()
+            .scan(0, |sum: &mut usize, &count: &usize| {
+                *sum += count;
+                Some(*sum - count)
+            })
+            .collect()
+    }
+
+    /// Count the opaque pixels indexed to each centroid of a result and
+    /// calculate the share of the opaque pixels each centroid represents.
+    /// Returns a Vec of `CentroidData` in centroid order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the result does not index every opaque pixel of the view.
+    pub fn centroid_data(&self, result: &Kmeans<[f32; 3]>) -> Vec<CentroidData<[f32; 3]>> {
+        assert_eq!(result.indices.len(), self.opaque_count());
+        CentroidData::from_indices(&result.centroids, &result.indices)
+    }
+
+    /// Sum of the differences between each opaque pixel and its indexed
+    /// centroid of a result, read from the buffer without copying it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the result does not index every opaque pixel of the view.
+    pub fn inertia(&self, result: &Kmeans<[f32; 3]>) -> f32 {
+        assert_eq!(result.indices.len(), self.opaque_count());
+        let channels: usize = self.channels;
+
+        self.rows()
+            .zip(self.row_offsets().par_iter())
+            .map(|(row, &offset): (&[u8], &usize)| {
+                row.chunks_exact(channels)
+                    .filter(|p: &&[u8]| self.is_opaque(p))
+                    .zip(result.indices[offset..].iter())
+                    .map(|(p, &index): (&[u8], &u8)| {
+                        <[f32; 3]>::difference(
+                            &[p[0] as f32, p[1] as f32, p[2] as f32],
+                            &result.centroids[index as usize],
+                        )
+                    })
+                    .sum::<f32>()
+            })
+            .sum()
+    }
+
+    /// Replace each opaque pixel with its indexed palette color, producing a
+    /// tightly packed buffer with the same number of channels. Colors are
+    /// rounded and clamped to `0..=255`, the alpha channel is kept, and
+    /// transparent pixels are copied unchanged.
+    pub fn remap(&self, palette: &[[f32; 3]], indices: &[u8]) -> Vec<u8> {
+        let channels: usize = self.channels;
+        let colors: Vec<[f32; 3]> = <[f32; 3]>::map_indices_to_centroids(palette, indices);
+
+        self.rows()
+            .zip(self.row_offsets().par_iter())
+            .flat_map_iter(|(row, &offset): (&[u8], &usize)| {
+                let mut row_colors = colors[offset.min(colors.len())..].iter();
+                row.chunks_exact(channels).flat_map(move |pixel: &[u8]| {
+                    let color: Option<&[f32; 3]> = if self.is_opaque(pixel) {
+                        row_colors.next()
+                    } else {
+                        None
+                    };
+                    let rgb: [u8; 3] = match color {
+                        Some(color) => color.map(|c: f32| c.round().clamp(0.0, 255.0) as u8),
+                        None => [pixel[0], pixel[1], pixel[2]],
+                    };
+                    rgb.into_iter().chain(pixel.get(3).copied())
+                })
+            })
+            .collect()
+    }
+}
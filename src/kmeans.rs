@@ -1,6 +1,8 @@
 use rand::{rngs::SmallRng, thread_rng, Rng, SeedableRng};
 use rayon::prelude::*;
 
+use crate::metric::Metric;
+
 /// A trait for enabling k-means calculation of a data type.
 pub trait Calculate: Sized {
     /// Find a points's nearest centroid, index the point with that centroid.
@@ -9,11 +11,7 @@ pub trait Calculate: Sized {
     /// Find the new centroid locations based on the average of the points that
     /// correspond to the centroid. If no points correspond, the centroid is
     /// re-initialized with a random point.
-    fn recalculate_centroids(
-        buf: &[Self],
-        centroids: &mut [Self],
-        indices: &[u8],
-    );
+    fn recalculate_centroids(buf: &[Self], centroids: &mut [Self], indices: &[u8]);
 
     /// Calculate the distance metric for convergence comparison.
     fn check_loop(centroids: &[Self], old_centroids: &[Self]) -> f32;
@@ -22,7 +20,8 @@ pub trait Calculate: Sized {
     fn create_random(rng: &mut impl Rng) -> Self;
 
     /// Calculate the geometric distance between two points, the square root is
-    /// omitted.
+    /// omitted. This distance is unweighted, use a [`Metric`](trait.Metric.html)
+    /// such as `WeightedEuclidean` for per-channel weights.
     fn difference(c1: &Self, c2: &Self) -> f32;
 }
 
@@ -34,19 +33,93 @@ pub struct Kmeans<C: Calculate> {
     pub score: f32,
     /// Points determined to be centroids of input buffer.
     pub centroids: Vec<C>,
-    /// Buffer of points indexed to centroids.
+    /// Buffer of points indexed to centroids. Empty for the results of
+    /// incremental clustering, such as
+    /// [`Octree::finalize`](struct.Octree.html#method.finalize), which index
+    /// their chunks in a separate pass.
     pub indices: Vec<u8>,
+    /// Flag for each centroid that was held fixed during the calculation,
+    /// empty if no centroids were locked.
+    pub(crate) locked: Vec<bool>,
 }
 
 impl<C: Calculate> Kmeans<C> {
     /// Create a new `Kmeans` struct to contain k-means results.
     pub fn new() -> Self {
         Kmeans {
-            score: core::f32::MAX,
+            score: f32::MAX,
             centroids: Vec::new(),
             indices: Vec::new(),
+            locked: Vec::new(),
         }
     }
+
+    /// Returns `true` if the centroid at `index` was held fixed during the
+    /// calculation.
+    pub fn is_locked(&self, index: usize) -> bool {
+        self.locked.get(index).copied().unwrap_or(false)
+    }
+
+    /// Returns a flag for each centroid that was held fixed during the
+    /// calculation, or an empty slice if no centroids were locked.
+    pub fn locked(&self) -> &[bool] {
+        &self.locked
+    }
+
+    /// Reorder the centroids by `permutation` and relabel the indexed buffer
+    /// and locked flags to match, where the `n`-th element of the permutation
+    /// is the old index of the centroid moved to position `n`. Permutations
+    /// are returned by [`sort_permutation`][sort]. `CentroidData` of the
+    /// result is relabeled with [`relabel_data`][data].
+    ///
+    /// [sort]: fn.sort_permutation.html
+    /// [data]: fn.relabel_data.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if `permutation` is not a permutation of the centroid indices.
+    pub fn reorder(&mut self, permutation: &[u8])
+    where
+        C: Clone,
+    {
+        let k: usize = self.centroids.len();
+        let mut seen: Vec<bool> = vec![false; k];
+        assert!(
+            permutation.len() == k
+                && permutation.iter().all(|&i: &u8| {
+                    let fresh: bool = (i as usize) < k && !seen[i as usize];
+                    if fresh {
+                        seen[i as usize] = true;
+                    }
+                    fresh
+                }),
+            "Invalid centroid permutation."
+        );
+
+        self.centroids = permutation
+            .iter()
+            .map(|&i: &u8| self.centroids[i as usize].clone())
+            .collect();
+        if !self.locked.is_empty() {
+            self.locked = permutation
+                .iter()
+                .map(|&i: &u8| self.is_locked(i as usize))
+                .collect();
+        }
+        crate::sort::relabel(&mut self.indices, permutation);
+    }
+
+    /// Sum of the differences between each point of `buf` and its indexed
+    /// centroid, also known as the within-cluster sum of squares.
+    pub fn inertia(&self, buf: &[C]) -> f32
+    where
+        C: Sync,
+    {
+        buf.par_iter()
+            .zip(self.indices.par_iter())
+            .map(|(point, &index): (&C, &u8)| C::difference(point, &self.centroids[index as usize]))
+            .sum()
+    }
 }
 
 /// Find the k-means centroids of a buffer.
@@ -108,6 +181,173 @@ pub fn get_kmeans<C: Calculate + Clone + Sync + Send>(
         score,
         centroids,
         indices,
+        locked: Vec::new(),
+    }
+}
+
+/// Find the k-means centroids of a buffer using a custom distance
+/// [`Metric`](trait.Metric.html). Takes the same arguments as
+/// [`get_kmeans`](fn.get_kmeans.html) along with the metric.
+///
+/// The metric is used for k-means++ initialization, assigning points to their
+/// nearest centroid, and the convergence score. Centroids are recalculated
+/// with [`Calculate::recalculate_centroids`][recalc].
+///
+/// [recalc]: trait.Calculate.html#tymethod.recalculate_centroids
+pub fn get_kmeans_with_metric<C, M>(
+    k: usize,
+    max_iter: usize,
+    converge: f32,
+    verbose: bool,
+    buf: &[C],
+    metric: &M,
+) -> Kmeans<C>
+where
+    C: Calculate + Clone + Sync + Send,
+    M: Metric<C>,
+{
+    // Initialize the random centroids
+    let mut rng: SmallRng = SmallRng::from_rng(thread_rng()).unwrap();
+    let mut centroids: Vec<C> = Vec::with_capacity(k);
+    crate::plus_plus::init_plus_plus_with_metric(k, &mut rng, buf, &mut centroids, metric);
+
+    lloyd(
+        centroids,
+        max_iter,
+        converge,
+        verbose,
+        buf,
+        metric,
+        |buf: &[C], centroids: &[C], indices: &mut Vec<u8>| {
+            get_closest_centroid_with_metric(buf, centroids, indices, metric)
+        },
+        C::recalculate_centroids,
+    )
+}
+
+/// Find the k-means centroids of a buffer starting from the given centroids
+/// instead of k-means++ initialization. Takes the same arguments as
+/// [`get_kmeans`](fn.get_kmeans.html) with the initial centroids in place of
+/// `k`.
+///
+/// Deterministic quantizers like [`get_median_cut`](fn.get_median_cut.html)
+/// make good seeds, which k-means then refines in few iterations.
+pub fn get_kmeans_seeded<C: Calculate + Clone + Sync + Send>(
+    centroids: Vec<C>,
+    max_iter: usize,
+    converge: f32,
+    verbose: bool,
+    buf: &[C],
+) -> Kmeans<C> {
+    lloyd(
+        centroids,
+        max_iter,
+        converge,
+        verbose,
+        buf,
+        &|c1: &C, c2: &C| C::difference(c1, c2),
+        C::get_closest_centroid,
+        C::recalculate_centroids,
+    )
+}
+
+/// Find the index of a point's nearest centroid and its difference.
+#[inline]
+pub(crate) fn closest_centroid<C, M: Metric<C>>(
+    point: &C,
+    centroids: &[C],
+    metric: &M,
+) -> (usize, f32) {
+    centroids.iter().enumerate().fold(
+        (0, f32::INFINITY),
+        |(idx, min): (usize, f32), (j, c): (usize, &C)| {
+            let diff: f32 = metric.difference(point, c);
+            if diff < min {
+                (j, diff)
+            } else {
+                (idx, min)
+            }
+        },
+    )
+}
+
+/// Index each point of the buffer with its nearest centroid under `metric`.
+pub(crate) fn get_closest_centroid_with_metric<C: Sync, M: Metric<C>>(
+    buf: &[C],
+    centroids: &[C],
+    indices: &mut Vec<u8>,
+    metric: &M,
+) {
+    indices.par_extend(
+        buf.par_iter()
+            .map(|point: &C| closest_centroid(point, centroids, metric).0 as u8),
+    );
+}
+
+/// Sum of the metric differences between centroids and their previous
+/// locations.
+pub(crate) fn check_loop_with_metric<C: Sync, M: Metric<C>>(
+    centroids: &[C],
+    old_centroids: &[C],
+    metric: &M,
+) -> f32 {
+    centroids
+        .par_iter()
+        .zip(old_centroids.par_iter())
+        .map(|(c1, c2): (&C, &C)| metric.difference(c1, c2))
+        .sum::<f32>()
+}
+
+/// Lloyd's algorithm main loop with pluggable assignment and update steps,
+/// starting from already initialized centroids.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn lloyd<C, M, A, U>(
+    mut centroids: Vec<C>,
+    max_iter: usize,
+    converge: f32,
+    verbose: bool,
+    buf: &[C],
+    metric: &M,
+    mut assign: A,
+    mut update: U,
+) -> Kmeans<C>
+where
+    C: Calculate + Clone + Sync,
+    M: Metric<C>,
+    A: FnMut(&[C], &[C], &mut Vec<u8>),
+    U: FnMut(&[C], &mut [C], &[u8]),
+{
+    let mut iterations: usize = 0;
+    let mut score: f32;
+    let mut old_centroids: Vec<C> = centroids.clone();
+    let mut indices: Vec<u8> = Vec::with_capacity(buf.len());
+
+    loop {
+        assign(buf, &centroids, &mut indices);
+        update(buf, &mut centroids, &indices);
+
+        score = check_loop_with_metric(&centroids, &old_centroids, metric);
+        if verbose {
+            println!("Score: {}", score);
+        }
+
+        if iterations >= max_iter || score <= converge {
+            if verbose {
+                println!("Iterations: {}", iterations);
+            }
+            break;
+        }
+
+        indices.clear();
+        iterations += 1;
+        old_centroids.clone_from(&centroids);
+    }
+
+    Kmeans {
+        score,
+        centroids,
+        indices,
+        locked: Vec::new(),
     }
 }
 
@@ -224,8 +464,30 @@ pub fn get_kmeans_hamerly<C: Hamerly + Clone>(
 ) -> Kmeans<C> {
     // Initialize the random centroids
     let mut rng: SmallRng = SmallRng::from_rng(thread_rng()).unwrap();
-    let mut centers: HamerlyCentroids<C> = HamerlyCentroids::new(k);
-    crate::plus_plus::init_plus_plus(k, &mut rng, buf, &mut centers.centroids);
+    let mut centroids: Vec<C> = Vec::with_capacity(k);
+    crate::plus_plus::init_plus_plus(k, &mut rng, buf, &mut centroids);
+
+    get_kmeans_hamerly_seeded(centroids, max_iter, converge, verbose, buf)
+}
+
+/// Find the k-means centroids of a buffer using the Hamerly algorithm,
+/// starting from the given centroids instead of k-means++ initialization.
+/// Takes the same arguments as
+/// [`get_kmeans_hamerly`](fn.get_kmeans_hamerly.html) with the initial
+/// centroids in place of `k`.
+///
+/// Deterministic quantizers like [`get_wu`](fn.get_wu.html) make good seeds,
+/// which k-means then refines in few iterations.
+pub fn get_kmeans_hamerly_seeded<C: Hamerly + Clone>(
+    centroids: Vec<C>,
+    max_iter: usize,
+    converge: f32,
+    verbose: bool,
+    buf: &[C],
+) -> Kmeans<C> {
+    let mut rng: SmallRng = SmallRng::from_rng(thread_rng()).unwrap();
+    let mut centers: HamerlyCentroids<C> = HamerlyCentroids::new(centroids.len());
+    centers.centroids = centroids;
 
     // Initialize points buffer and convergence variables
     let mut iterations: usize = 0;
@@ -266,5 +528,345 @@ pub fn get_kmeans_hamerly<C: Hamerly + Clone>(
         score,
         centroids: centers.centroids,
         indices: points.par_iter().map(|x: &HamerlyPoint| x.index).collect(),
+        locked: Vec::new(),
+    }
+}
+
+/// Find the k-means centroids of a buffer using the Hamerly algorithm and a
+/// custom distance [`Metric`](trait.Metric.html). Takes the same arguments as
+/// [`get_kmeans_with_metric`](fn.get_kmeans_with_metric.html).
+///
+/// The Hamerly bounds are computed from the metric's `distance`. If the metric
+/// does not declare that it satisfies the triangle inequality, the bounds
+/// would be invalid and the calculation falls back to
+/// [`get_kmeans_with_metric`](fn.get_kmeans_with_metric.html).
+pub fn get_kmeans_hamerly_with_metric<C, M>(
+    k: usize,
+    max_iter: usize,
+    converge: f32,
+    verbose: bool,
+    buf: &[C],
+    metric: &M,
+) -> Kmeans<C>
+where
+    C: Calculate + Clone + Sync + Send,
+    M: Metric<C>,
+{
+    if !metric.triangle_inequality() {
+        return get_kmeans_with_metric(k, max_iter, converge, verbose, buf, metric);
+    }
+
+    // Initialize the random centroids
+    let mut rng: SmallRng = SmallRng::from_rng(thread_rng()).unwrap();
+    let mut centroids: Vec<C> = Vec::with_capacity(k);
+    crate::plus_plus::init_plus_plus_with_metric(k, &mut rng, buf, &mut centroids, metric);
+
+    hamerly(
+        centroids,
+        max_iter,
+        converge,
+        verbose,
+        buf,
+        metric,
+        C::recalculate_centroids,
+    )
+}
+
+/// Hamerly's algorithm main loop with a pluggable update step, starting from
+/// already initialized centroids. `metric` must satisfy the triangle
+/// inequality.
+pub(crate) fn hamerly<C, M, U>(
+    mut centroids: Vec<C>,
+    max_iter: usize,
+    converge: f32,
+    verbose: bool,
+    buf: &[C],
+    metric: &M,
+    mut update: U,
+) -> Kmeans<C>
+where
+    C: Calculate + Clone + Sync,
+    M: Metric<C>,
+    U: FnMut(&[C], &mut [C], &[u8]),
+{
+    let k: usize = centroids.len();
+    let mut iterations: usize = 0;
+    let mut score: f32;
+    let mut old_centroids: Vec<C> = centroids.clone();
+    let mut half_distances: Vec<f32> = vec![0.0; k];
+    let mut deltas: Vec<f32> = vec![0.0; k];
+    let mut points: Vec<HamerlyPoint> = vec![HamerlyPoint::new(); buf.len()];
+    let mut indices: Vec<u8>;
+
+    loop {
+        // Half the distance from each centroid to its nearest other centroid
+        half_distances.par_iter_mut().enumerate().for_each(
+            |(idx, half_dist): (usize, &mut f32)| {
+                let min_dist: f32 = centroids
+                    .iter()
+                    .enumerate()
+                    .filter(|&(jdx, _): &(usize, &C)| idx != jdx)
+                    .fold(f32::MAX, |min: f32, (_, c): (usize, &C)| {
+                        min.min(metric.distance(&centroids[idx], c))
+                    });
+                *half_dist = 0.5 * min_dist;
+            },
+        );
+
+        points.par_iter_mut().zip(buf.par_iter()).for_each(
+            |(point, color): (&mut HamerlyPoint, &C)| {
+                let z: f32 = half_distances[point.index as usize].max(point.lower_bound);
+                if point.upper_bound <= z {
+                    return;
+                }
+
+                // Tighten upper bound
+                point.upper_bound = metric.distance(color, &centroids[point.index as usize]);
+                if point.upper_bound <= z {
+                    return;
+                }
+
+                // Find the two closest centers to current point
+                let (mut c1, mut min1, mut min2): (usize, f32, f32) = (0, f32::MAX, f32::MAX);
+                centroids
+                    .iter()
+                    .enumerate()
+                    .for_each(|(j, c): (usize, &C)| {
+                        let dist: f32 = metric.distance(color, c);
+                        if dist < min1 {
+                            min2 = min1;
+                            min1 = dist;
+                            c1 = j;
+                        } else if dist < min2 {
+                            min2 = dist;
+                        }
+                    });
+
+                point.index = c1 as u8;
+                point.upper_bound = min1;
+                point.lower_bound = min2;
+            },
+        );
+
+        indices = points.par_iter().map(|x: &HamerlyPoint| x.index).collect();
+        update(buf, &mut centroids, &indices);
+
+        deltas
+            .par_iter_mut()
+            .zip(centroids.par_iter().zip(old_centroids.par_iter()))
+            .for_each(|(delta, (c1, c2)): (&mut f32, (&C, &C))| {
+                *delta = metric.distance(c1, c2);
+            });
+
+        score = check_loop_with_metric(&centroids, &old_centroids, metric);
+        if verbose {
+            println!("Score: {}", score);
+        }
+
+        if iterations >= max_iter || score <= converge {
+            if verbose {
+                println!("Iterations: {}", iterations);
+            }
+            break;
+        }
+
+        // Update the bounds by the distance the centroids moved
+        let delta_p: f32 = deltas.iter().fold(0.0, |max: f32, &d: &f32| max.max(d));
+        points.par_iter_mut().for_each(|point: &mut HamerlyPoint| {
+            point.upper_bound += deltas[point.index as usize];
+            point.lower_bound -= delta_p;
+        });
+
+        old_centroids.clone_from(&centroids);
+        iterations += 1;
+    }
+
+    Kmeans {
+        score,
+        centroids,
+        indices,
+        locked: Vec::new(),
+    }
+}
+
+/// Restore the locked centroids at the start of `centroids`.
+fn restore_locked<C: Clone>(centroids: &mut [C], locked: &[C]) {
+    centroids
+        .iter_mut()
+        .zip(locked.iter())
+        .for_each(|(c, l): (&mut C, &C)| c.clone_from(l));
+}
+
+/// Find the k-means centroids of a buffer while holding some centroids fixed.
+/// Takes the same arguments as [`get_kmeans`](fn.get_kmeans.html) along with
+/// the locked centroids.
+///
+/// The locked centroids are the first centroids of the result and are never
+/// moved, but points are still assigned to them. The remaining
+/// `k - locked.len()` centroids are initialized with k-means++ with respect to
+/// the locked ones and optimized as usual. [`Kmeans::locked`][flags] of the
+/// result marks which centroids were held fixed.
+///
+/// [flags]: struct.Kmeans.html#method.locked
+///
+/// - `k` - number of clusters, including the locked centroids.
+/// - `locked` - centroids that must stay fixed.
+/// - `max_iter` - maximum number of iterations.
+/// - `converge` - threshold for convergence.
+/// - `verbose` - flag for printing convergence information to console.
+/// - `buf` - array of points.
+///
+/// # Panics
+///
+/// Panics if there are more locked centroids than `k` or if buffer is empty
+/// and centroids remain to be initialized.
+///
+/// ```
+/// use kmeans_colors::{get_kmeans_hamerly_locked, get_kmeans_locked};
+///
+/// let buf = [[2.0; 3], [4.0; 3], [250.0; 3], [252.0; 3]];
+/// let locked = [[0.0; 3]];
+/// [
+///     get_kmeans_locked(2, &locked, 20, 0.0, false, &buf),
+///     get_kmeans_hamerly_locked(2, &locked, 20, 0.0, false, &buf),
+/// ]
+/// .iter()
+/// .for_each(|result| {
+///     // The locked centroid is first and keeps its points without moving
+///     assert_eq!(result.centroids, [[0.0; 3], [251.0; 3]]);
+///     assert_eq!(result.indices, [0, 0, 1, 1]);
+///     assert!(result.is_locked(0));
+///     assert!(!result.is_locked(1));
+///     assert_eq!(result.locked(), [true, false]);
+/// });
+/// ```
+pub fn get_kmeans_locked<C: Calculate + Clone + Sync + Send>(
+    k: usize,
+    locked: &[C],
+    max_iter: usize,
+    converge: f32,
+    verbose: bool,
+    buf: &[C],
+) -> Kmeans<C> {
+    assert!(locked.len() <= k);
+
+    // Initialize the random centroids around the locked ones
+    let mut rng: SmallRng = SmallRng::from_rng(thread_rng()).unwrap();
+    let mut centroids: Vec<C> = Vec::with_capacity(k);
+    centroids.extend_from_slice(locked);
+    crate::plus_plus::extend_plus_plus(k, &mut rng, buf, &mut centroids, &|c1: &C, c2: &C| {
+        C::difference(c1, c2)
+    });
+
+    let mut result: Kmeans<C> = lloyd(
+        centroids,
+        max_iter,
+        converge,
+        verbose,
+        buf,
+        &|c1: &C, c2: &C| C::difference(c1, c2),
+        C::get_closest_centroid,
+        |buf: &[C], centroids: &mut [C], indices: &[u8]| {
+            C::recalculate_centroids(buf, centroids, indices);
+            restore_locked(centroids, locked);
+        },
+    );
+    result.locked = (0..result.centroids.len())
+        .map(|i: usize| i < locked.len())
+        .collect();
+    result
+}
+
+/// Find the k-means centroids of a buffer using the Hamerly algorithm while
+/// holding some centroids fixed. Takes the same arguments as
+/// [`get_kmeans_locked`](fn.get_kmeans_locked.html) and produces the same
+/// results.
+///
+/// # Panics
+///
+/// Panics if there are more locked centroids than `k` or if buffer is empty
+/// and centroids remain to be initialized.
+///
+/// ```
+/// use kmeans_colors::get_kmeans_hamerly_locked;
+///
+/// // A flat buffer has too few distinct colors for `k`
+/// let result = get_kmeans_hamerly_locked(3, &[[255.0; 3]], 20, 0.0, false, &[[1.0; 3]; 5]);
+/// assert_eq!(result.centroids, [[255.0; 3], [1.0; 3]]);
+/// assert_eq!(result.indices, [1; 5]);
+/// assert_eq!(result.locked(), [true, false]);
+/// ```
+pub fn get_kmeans_hamerly_locked<C: Hamerly + Clone>(
+    k: usize,
+    locked: &[C],
+    max_iter: usize,
+    converge: f32,
+    verbose: bool,
+    buf: &[C],
+) -> Kmeans<C> {
+    assert!(locked.len() <= k);
+
+    // Initialize the random centroids around the locked ones
+    let mut rng: SmallRng = SmallRng::from_rng(thread_rng()).unwrap();
+    let mut centroids: Vec<C> = Vec::with_capacity(k);
+    centroids.extend_from_slice(locked);
+    crate::plus_plus::extend_plus_plus(k, &mut rng, buf, &mut centroids, &|c1: &C, c2: &C| {
+        C::difference(c1, c2)
+    });
+
+    // Buffers with fewer distinct points than `k` produce fewer centroids
+    let mut centers: HamerlyCentroids<C> = HamerlyCentroids::new(centroids.len());
+    centers.centroids = centroids;
+
+    // Initialize points buffer and convergence variables
+    let mut iterations: usize = 0;
+    let mut score: f32;
+    let mut old_centers: Vec<C> = centers.centroids.clone();
+    let mut points: Vec<HamerlyPoint> = (0..buf.len())
+        .into_par_iter()
+        .map(|_| HamerlyPoint::new())
+        .collect();
+
+    // Main loop: find nearest centroids and recalculate means until convergence
+    loop {
+        C::compute_half_distances(&mut centers);
+        C::get_closest_centroid_hamerly(buf, &centers, &mut points);
+        C::recalculate_centroids_hamerly(&mut rng, buf, &mut centers, &points);
+
+        // Locked centroids have not moved
+        restore_locked(&mut centers.centroids, locked);
+        centers
+            .deltas
+            .iter_mut()
+            .take(locked.len())
+            .for_each(|delta: &mut f32| *delta = 0.0);
+
+        score = Calculate::check_loop(&centers.centroids, &old_centers);
+        if verbose {
+            println!("Score: {}", score);
+        }
+
+        // Verify that either the maximum iteration count has been met or the
+        // centroids haven't moved beyond a certain threshold since the
+        // previous iteration.
+        if iterations >= max_iter || score <= converge {
+            if verbose {
+                println!("Iterations: {}", iterations);
+            }
+            break;
+        }
+
+        C::update_bounds(&centers, &mut points);
+        old_centers.clone_from(&centers.centroids);
+        iterations += 1;
+    }
+
+    Kmeans {
+        score,
+        locked: (0..centers.centroids.len())
+            .map(|i: usize| i < locked.len())
+            .collect(),
+        centroids: centers.centroids,
+        indices: points.par_iter().map(|x: &HamerlyPoint| x.index).collect(),
     }
 }
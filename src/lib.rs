@@ -45,18 +45,19 @@
 //! results should match almost exactly.
 //!
 //! Note: If k-means calculation is taking too long, try scaling down the
-//! image size. A full-size image is not required for calculating the color
-//! palette or dominant color.
+//! image size or fitting the centroids on a subset of the pixels with
+//! [`get_kmeans_subsampled`](fn.get_kmeans_subsampled.html). A full-size image
+//! is not required for calculating the color palette or dominant color.
 //!
 //! ### Calculating k-means
 //!
 //! A basic workflow consists of reading a pixel buffer in, converting it into a
 //! flat array, then using that array with the k-means functions. The following
-//! example converts an array of `u8` into `Lab` colors then finds the k-means.
+//! example converts an array of `u8` into `[f32; 3]` RGB colors then finds the
+//! k-means.
 //!
 //! ```
-//! use palette::{FromColor, IntoColor, Lab, Pixel, Srgb};
-//! use kmeans_colors::{get_kmeans, Calculate, Kmeans, MapColor, Sort};
+//! use kmeans_colors::{get_kmeans, Kmeans, MapColor};
 //!
 //! // An image buffer of one black pixel and one white pixel
 //! let img_vec = [0u8, 0, 0, 255, 255, 255];
@@ -66,58 +67,34 @@
 //! # let max_iter = 20;
 //! # let converge = 8.0;
 //! # let verbose = false;
-//! # let seed = 0;
-//! // Convert RGB [u8] buffer to Lab for k-means
-//! let lab: Vec<Lab> = Srgb::from_raw_slice(&img_vec)
-//!     .iter()
-//!     .map(|x| x.into_format().into_color())
+//! // Convert RGB [u8] buffer to [f32; 3] for k-means
+//! let rgb: Vec<[f32; 3]> = img_vec
+//!     .chunks_exact(3)
+//!     .map(|x| [x[0] as f32, x[1] as f32, x[2] as f32])
 //!     .collect();
 //!
 //! // Iterate over the runs, keep the best results
 //! let mut result = Kmeans::new();
-//! for i in 0..runs {
-//!     let run_result = get_kmeans(
-//!         k,
-//!         max_iter,
-//!         converge,
-//!         verbose,
-//!         &lab,
-//!         seed + i as u64,
-//!     );
+//! for _ in 0..runs {
+//!     let run_result = get_kmeans(k, max_iter, converge, verbose, &rgb);
 //!     if run_result.score < result.score {
 //!         result = run_result;
 //!     }
 //! }
 //!
-//! // Convert indexed colors back to Srgb<u8> for output
-//! let rgb = &result.centroids
-//!     .iter()
-//!     .map(|x| Srgb::from_color(*x).into_format())
-//!     .collect::<Vec<Srgb<u8>>>();
-//! let buffer = Srgb::map_indices_to_centroids(&rgb, &result.indices);
-//! # assert_eq!(Srgb::into_raw_slice(&buffer), [119, 119, 119, 119, 119, 119]);
+//! // Map the indexed colors back to their centroids for output
+//! let buffer = <[f32; 3]>::map_indices_to_centroids(&result.centroids, &result.indices);
+//! # assert_eq!(buffer, [[127.5; 3]; 2]);
 //! # // Test get_kmeans_hamerly
 //! # let mut result = Kmeans::new();
-//! # for i in 0..runs {
-//! #     let run_result = kmeans_colors::get_kmeans_hamerly(
-//! #         k,
-//! #         max_iter,
-//! #         converge,
-//! #         verbose,
-//! #         &lab,
-//! #         seed + i as u64,
-//! #     );
+//! # for _ in 0..runs {
+//! #     let run_result = kmeans_colors::get_kmeans_hamerly(k, max_iter, converge, verbose, &rgb);
 //! #     if run_result.score < result.score {
 //! #         result = run_result;
 //! #     }
 //! # }
-//! # // Convert indexed colors back to Srgb<u8> for output
-//! # let rgb = &result.centroids
-//! #     .iter()
-//! #     .map(|x| Srgb::from_color(*x).into_format())
-//! #     .collect::<Vec<Srgb<u8>>>();
-//! # let buffer = Srgb::map_indices_to_centroids(&rgb, &result.indices);
-//! # assert_eq!(Srgb::into_raw_slice(&buffer), [119, 119, 119, 119, 119, 119]);
+//! # let buffer = <[f32; 3]>::map_indices_to_centroids(&result.centroids, &result.indices);
+//! # assert_eq!(buffer, [[127.5; 3]; 2]);
 //! ```
 //!
 //! k-means++ is used for centroid initialization. Because the initialization is
@@ -136,65 +113,240 @@
 //!
 //! After k-means calculation, the dominant color can be found by sorting the
 //! results and taking the centroid of the first item. The
-//! [`sort_indexed_colors`][sort] function sorts the colors from darkest to
-//! lightest and returns an array of [`CentroidData`](struct.CentroidData.html).
+//! [`sort_indexed_colors`][sort] function sorts the colors by lightness from
+//! darkest to lightest and returns an array of
+//! [`CentroidData`](struct.CentroidData.html) with the count and percentage of
+//! each centroid.
+//! [`CentroidData::from_indices`][from_indices] produces the same data in
+//! centroid order.
 //!
-//! [sort]: trait.Sort.html#tymethod.sort_indexed_colors
+//! [sort]: trait.Sort.html#method.sort_indexed_colors
+//! [from_indices]: struct.CentroidData.html#method.from_indices
 //! ```
-//! # use palette::{FromColor, IntoColor, Lab, Pixel, Srgb};
 //! # use kmeans_colors::{get_kmeans, Kmeans};
 //! use kmeans_colors::Sort;
 //!
 //! # let img_vec = [0u8, 0, 0, 255, 255, 255];
-//! # let runs = 1;
 //! # let k = 1;
 //! # let max_iter = 20;
 //! # let converge = 8.0;
 //! # let verbose = false;
-//! # let seed = 0;
-//! # let lab: Vec<Lab> = Srgb::from_raw_slice(&img_vec)
-//! #    .iter()
-//! #    .map(|x| x.into_format().into_color())
-//! #    .collect();
-//! # let mut result = Kmeans::new();
-//! # for i in 0..runs {
-//! #     let run_result = get_kmeans(
-//! #         k,
-//! #         max_iter,
-//! #         converge,
-//! #         verbose,
-//! #         &lab,
-//! #         seed + i as u64,
-//! #     );
-//! #     if run_result.score < result.score {
-//! #         result = run_result;
-//! #     }
-//! # }
+//! # let rgb: Vec<[f32; 3]> = img_vec
+//! #     .chunks_exact(3)
+//! #     .map(|x| [x[0] as f32, x[1] as f32, x[2] as f32])
+//! #     .collect();
+//! # let result = get_kmeans(k, max_iter, converge, verbose, &rgb);
 //! // Using the results from the previous example, process the centroid data
-//! let mut res = Lab::sort_indexed_colors(&result.centroids, &result.indices);
+//! let mut res = <[f32; 3]>::sort_indexed_colors(&result.centroids, &result.indices);
 //!
 //! // We can find the dominant color directly
-//! let dominant_color = Lab::get_dominant_color(&res);
-//! # assert_eq!(
-//! #    Srgb::from_color(dominant_color.unwrap()).into_format::<u8>(),
-//! #    Srgb::new(119u8, 119, 119)
-//! # );
+//! let dominant_color = <[f32; 3]>::get_dominant_color(&res);
+//! # assert_eq!(dominant_color, Some([127.5; 3]));
 //!
 //! // Or we can manually sort the vec by percentage, and the most appearing
 //! // color will be the first element
 //! res.sort_unstable_by(|a, b| (b.percentage).partial_cmp(&a.percentage).unwrap());
 //! let dominant_color = res.first().unwrap().centroid;
 //! ```
+//!
+//! Palettes can also be ordered by lightness, hue, share, chroma, or along a
+//! path of similar colors with [`sort_permutation`][permutation], which
+//! returns the new order of the centroid indices. [`Kmeans::reorder`][reorder]
+//! applies the order to a result while keeping its indices valid.
+//!
+//! [permutation]: fn.sort_permutation.html
+//! [reorder]: struct.Kmeans.html#method.reorder
+//! ```
+//! use kmeans_colors::{get_kmeans, sort_permutation, SortOrder};
+//!
+//! let rgb = [[255.0; 3], [0.0; 3], [250.0; 3], [5.0; 3]];
+//! let mut result = get_kmeans(2, 20, 0.0, false, &rgb);
+//! let permutation = sort_permutation(&result.centroids, &result.indices, SortOrder::Lightness);
+//! result.reorder(&permutation);
+//! assert_eq!(result.centroids, [[2.5; 3], [252.5; 3]]);
+//! assert_eq!(result.indices, [1, 0, 1, 0]);
+//! ```
+//!
+//! ## Distance metrics
+//!
+//! The [`Metric`](trait.Metric.html) trait decouples the distance used for
+//! clustering from the point type. [`get_kmeans_with_metric`][metric] and
+//! [`get_kmeans_hamerly_with_metric`][hamerly_metric] take a metric such as
+//! [`SquaredEuclidean`](struct.SquaredEuclidean.html),
+//! [`Manhattan`](struct.Manhattan.html), [`Cosine`](struct.Cosine.html),
+//! [`WeightedEuclidean`](struct.WeightedEuclidean.html), or a closure. The
+//! Hamerly optimization is only used for metrics that satisfy the triangle
+//! inequality.
+//!
+//! `Calculate` and `Hamerly` are implemented for `[f32; N]` points of any
+//! dimension. Per-channel weights, like the `REC601` luma weights for RGB or
+//! [`WeightedEuclidean::lab`][lab] for trading lightness against chroma, are
+//! applied through `WeightedEuclidean` without preprocessing the buffer. The
+//! weights only apply to the `_with_metric` functions, which use the metric
+//! for assignment, k-means++ seeding, and the Hamerly bounds.
+//! `Calculate::difference`, [`init_plus_plus`](fn.init_plus_plus.html), and
+//! the `Hamerly` trait remain unweighted.
+//!
+//! [lab]: struct.WeightedEuclidean.html#method.lab
+//!
+//! ## Spherical k-means
+//!
+//! For normalized embeddings, [`get_kmeans_spherical`][spherical] assigns
+//! points by cosine similarity and re-normalizes the mean centroids after each
+//! iteration.
+//!
+//! [spherical]: fn.get_kmeans_spherical.html
+//! ```
+//! use kmeans_colors::get_kmeans_spherical;
+//!
+//! let embeddings = [[1.0, 0.0], [0.9, 0.1], [0.0, 1.0], [0.1, 0.9]];
+//! let result = get_kmeans_spherical(2, 20, 0.0, false, &embeddings);
+//! assert_eq!(result.indices[0], result.indices[1]);
+//! assert_ne!(result.indices[1], result.indices[2]);
+//! ```
+//!
+//! [metric]: fn.get_kmeans_with_metric.html
+//! [hamerly_metric]: fn.get_kmeans_hamerly_with_metric.html
+//! ```
+//! use kmeans_colors::{get_kmeans_hamerly_with_metric, Manhattan};
+//!
+//! let rgb = [[0.0, 0.0, 0.0], [255.0, 255.0, 255.0]];
+//! let result = get_kmeans_hamerly_with_metric(2, 20, 0.0, false, &rgb, &Manhattan);
+//! assert_ne!(result.indices[0], result.indices[1]);
+//! ```
+//!
+//! ## Other algorithms
+//!
+//! - [`get_kmedoids`](fn.get_kmedoids.html) and
+//!   [`get_kmedoids_clara`](fn.get_kmedoids_clara.html) find k-medoids, whose
+//!   centroids are points of the input buffer, under any
+//!   `Calculate::difference`.
+//! - [`get_kmedians`](fn.get_kmedians.html) finds per-channel medians with the
+//!   Manhattan distance, which are robust to outlier points.
+//! - [`get_fuzzy_cmeans`](fn.get_fuzzy_cmeans.html) finds fuzzy c-means with
+//!   a soft membership of each point to every centroid.
+//! - [`get_gmm`](fn.get_gmm.html) fits a Gaussian mixture model with diagonal
+//!   or full covariances by expectation maximization.
+//! - [`get_bisecting_kmeans`](fn.get_bisecting_kmeans.html) builds a tree of
+//!   two-way splits which can be cut into palettes of any size.
+//! - [`merge_centroids`](fn.merge_centroids.html) merges the centroids of a
+//!   k-means result bottom-up into a dendrogram with Ward, average, or
+//!   complete linkage.
+//! - [`get_kmeans_locked`](fn.get_kmeans_locked.html) and
+//!   [`get_kmeans_hamerly_locked`](fn.get_kmeans_hamerly_locked.html) hold
+//!   required colors fixed while optimizing the rest of the palette.
+//! - [`get_kmeans_coreset`](fn.get_kmeans_coreset.html) clusters a small
+//!   weighted [`Coreset`](struct.Coreset.html) of a large buffer and indexes
+//!   the full buffer in a single pass.
+//! - [`StreamingKmeans`](struct.StreamingKmeans.html) updates centroids
+//!   incrementally from chunks of a buffer too large to hold in memory.
+//! - [`get_kmeans_balanced`](fn.get_kmeans_balanced.html) finds k-means with
+//!   a minimum and maximum number of points in each cluster.
+//! - [`get_median_cut`](fn.get_median_cut.html) quantizes a buffer or weighted
+//!   histogram deterministically without iterations. Its centroids can seed
+//!   [`get_kmeans_seeded`](fn.get_kmeans_seeded.html).
+//! - [`get_wu`](fn.get_wu.html) quantizes colors deterministically with Wu's
+//!   variance-minimizing cuts of a color histogram. Its centroids can seed
+//!   [`get_kmeans_hamerly_seeded`](fn.get_kmeans_hamerly_seeded.html).
+//! - [`Octree`](struct.Octree.html) quantizes colors with an octree built
+//!   incrementally from chunks of a buffer in bounded memory, and
+//!   [`get_octree`](fn.get_octree.html) quantizes a whole buffer at once.
+//!
+//! ## Selecting `k`
+//!
+//! [`select_k`](fn.select_k.html) runs k-means over a range of `k` and picks
+//! the best by elbow detection, mean silhouette, the gap statistic, or X-means
+//! splitting. The selected `k`, its result, and the score of each `k` are
+//! returned.
+//!
+//! ## Dithering
+//!
+//! [`MapColor`](trait.MapColor.html) replaces each pixel with its nearest
+//! centroid, which bands at low `k`. The [`Dither`](trait.Dither.html) trait
+//! indexes an image buffer onto a palette while diffusing the quantization
+//! error to neighboring pixels with the Floyd-Steinberg, Jarvis-Judice-Ninke,
+//! Stucki, Atkinson, or Sierra kernels. Ordered dithering with Bayer or blue
+//! noise threshold maps processes pixels in parallel and is stable across
+//! animation frames.
+//!
+//! ```
+//! use kmeans_colors::{Dither, ErrorDiffusion, Kernel, MapColor, OrderedDither, ThresholdMap};
+//!
+//! // A 4x1 image of mid gray mapped onto black and white
+//! let img = [[127.5; 3]; 4];
+//! let palette = [[0.0; 3], [255.0; 3]];
+//! let options = ErrorDiffusion::new(Kernel::FloydSteinberg);
+//! let indices = <[f32; 3]>::dither_error_diffusion(&img, 4, &palette, &options);
+//! assert_eq!(indices, [0, 1, 0, 1]);
+//!
+//! let dithered = <[f32; 3]>::map_indices_to_centroids(&palette, &indices);
+//! assert_eq!(dithered[1], [255.0; 3]);
+//!
+//! // The same pixels as a 2x2 image with a Bayer matrix
+//! let options = OrderedDither::new(ThresholdMap::Bayer2);
+//! let indices = <[f32; 3]>::dither_ordered(&img, 2, &palette, &options);
+//! assert_eq!(indices, [0, 1, 1, 0]);
+//! ```
+//!
+//! ## Image buffers
+//!
+//! [`ImageView`](struct.ImageView.html) wraps an 8-bit RGB or RGBA buffer
+//! with its width, height, and row stride. It converts the pixels for the
+//! k-means functions, indexes and dithers the image onto a palette with its
+//! geometry, computes centroid percentages and inertia directly from the
+//! buffer, and remaps the indices back into a buffer with the same channels.
+//! With an alpha threshold, transparent RGBA pixels are excluded from
+//! clustering and keep their original values when remapping.
 #![warn(missing_docs, rust_2018_idioms, unsafe_code)]
 
 mod colors;
 
+mod agglomerative;
+mod balanced;
+mod bisecting;
+mod coreset;
+mod dither;
+mod fuzzy;
+mod gmm;
+mod image;
 mod kmeans;
+mod median_cut;
+mod medians;
+mod medoids;
+mod metric;
+mod octree;
 mod plus_plus;
+mod select;
 mod sort;
+mod spherical;
+mod streaming;
+mod subsample;
+mod wu;
 
-pub use colors::MapColor;
+pub use agglomerative::{merge_centroids, Dendrogram, Linkage, Merge};
+pub use balanced::{get_closest_centroid_balanced, get_kmeans_balanced};
+pub use bisecting::{get_bisecting_kmeans, BisectingNode, BisectingTree, SplitCriterion};
+pub use colors::{sort_permutation, MapColor};
+pub use coreset::{build_coreset, get_kmeans_coreset, Coreset};
+pub use dither::{Dither, ErrorDiffusion, Kernel, OrderedDither, ThresholdMap};
 
-pub use kmeans::{get_kmeans, get_kmeans_hamerly, Calculate, Kmeans};
-pub use plus_plus::init_plus_plus;
-pub use sort::{CentroidData, Sort};
+pub use fuzzy::{get_fuzzy_cmeans, FuzzyCmeans};
+pub use gmm::{get_gmm, Covariance, Gmm, GmmInit};
+pub use image::ImageView;
+pub use kmeans::{
+    get_kmeans, get_kmeans_hamerly, get_kmeans_hamerly_locked, get_kmeans_hamerly_seeded,
+    get_kmeans_hamerly_with_metric, get_kmeans_locked, get_kmeans_seeded, get_kmeans_with_metric,
+    Calculate, Kmeans,
+};
+pub use median_cut::{get_median_cut, BoxSplit};
+pub use medians::{get_kmedians, recalculate_medians};
+pub use medoids::{get_kmedoids, get_kmedoids_clara};
+pub use metric::{Cosine, Manhattan, Metric, SquaredEuclidean, WeightedEuclidean};
+pub use octree::{get_octree, Octree};
+pub use plus_plus::{init_plus_plus, init_plus_plus_with_metric};
+pub use select::{select_k, silhouette, Criterion, Selection};
+pub use sort::{relabel, relabel_data, CentroidData, Sort, SortOrder};
+pub use spherical::{get_kmeans_spherical, init_plus_plus_spherical, normalize};
+pub use streaming::StreamingKmeans;
+pub use subsample::{get_kmeans_subsampled, subsample, Subsample};
+pub use wu::get_wu;
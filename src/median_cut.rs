@@ -0,0 +1,221 @@
+use std::cmp::Ordering;
+
+use rayon::prelude::*;
+
+use crate::kmeans::Kmeans;
+
+/// Criterion for choosing which box to split next in median cut.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BoxSplit {
+    /// Split the box with the largest weighted sum of squared errors along
+    /// the channel with the largest variance.
+    Variance,
+    /// Split the box with the largest extent along its longest channel.
+    Range,
+}
+
+/// A box of points in median cut.
+struct ColorBox<const N: usize> {
+    members: Vec<usize>,
+    mean: [f32; N],
+    sse: f32,
+    /// Channel to split along and the priority of the box, `0.0` if it
+    /// cannot be split.
+    axis: usize,
+    priority: f32,
+}
+
+impl<const N: usize> ColorBox<N> {
+    fn new(members: Vec<usize>, buf: &[[f32; N]], weights: &[f32], split: BoxSplit) -> Self {
+        let total: f32 = members.iter().map(|&i: &usize| weights[i]).sum::<f32>();
+        let mut mean: [f32; N] = [0.0; N];
+        let mut min: [f32; N] = [f32::INFINITY; N];
+        let mut max: [f32; N] = [f32::NEG_INFINITY; N];
+        members.iter().for_each(|&i: &usize| {
+            (0..N).for_each(|c: usize| {
+                mean[c] += weights[i] * buf[i][c];
+                min[c] = min[c].min(buf[i][c]);
+                max[c] = max[c].max(buf[i][c]);
+            });
+        });
+        mean.iter_mut()
+            .for_each(|m: &mut f32| *m /= total.max(f32::MIN_POSITIVE));
+
+        let mut variance: [f32; N] = [0.0; N];
+        members.iter().for_each(|&i: &usize| {
+            (0..N).for_each(|c: usize| {
+                let d: f32 = buf[i][c] - mean[c];
+                variance[c] += weights[i] * d * d;
+            });
+        });
+        let sse: f32 = variance.iter().sum();
+
+        let widest = |values: &[f32; N]| -> (usize, f32) {
+            values.iter().copied().enumerate().fold(
+                (0, 0.0),
+                |best: (usize, f32), (c, v): (usize, f32)| {
+                    if v > best.1 {
+                        (c, v)
+                    } else {
+                        best
+                    }
+                },
+            )
+        };
+        let (axis, priority): (usize, f32) = match split {
+            BoxSplit::Variance => (widest(&variance).0, sse),
+            BoxSplit::Range => {
+                let mut range: [f32; N] = [0.0; N];
+                (0..N).for_each(|c: usize| range[c] = max[c] - min[c]);
+                widest(&range)
+            }
+        };
+        // Boxes of identical points cannot be split
+        let splittable: bool = members.len() > 1 && (0..N).any(|c: usize| max[c] > min[c]);
+
+        ColorBox {
+            members,
+            mean,
+            sse,
+            axis,
+            priority: if splittable { priority } else { 0.0 },
+        }
+    }
+}
+
+/// Quantize a buffer into at most `k` colors with median cut.
+///
+/// Starting from a box around all points, the box chosen by `split` is
+/// repeatedly cut at the weighted median of one channel until there are `k`
+/// boxes or no box can be split. The centroids are the weighted means of the
+/// boxes and each point is indexed to its box. The result is deterministic
+/// and needs no iterations, and its centroids can seed
+/// [`get_kmeans_seeded`](fn.get_kmeans_seeded.html).
+///
+/// The `score` is the total weighted sum of squared errors of the boxes.
+///
+/// - `k` - maximum number of colors.
+/// - `split` - criterion for choosing the box to split.
+/// - `weights` - optional weight of each point, such as histogram counts.
+/// - `buf` - array of points.
+///
+/// # Panics
+///
+/// Panics if buffer is empty, if `k` is zero, or if `weights` has a different
+/// length than the buffer.
+///
+/// ```
+/// use kmeans_colors::{get_kmeans_seeded, get_median_cut, BoxSplit};
+///
+/// // Two rows of green with a single far red point
+/// let buf = [[0.0, 0.0], [0.0, 0.0], [0.0, 0.0], [0.0, 100.0], [0.0, 100.0], [0.0, 100.0], [120.0, 0.0]];
+///
+/// // Variance cuts the green channel, which varies the most
+/// let result = get_median_cut(2, BoxSplit::Variance, None, &buf);
+/// assert_eq!(result.indices, [0, 0, 0, 1, 1, 1, 0]);
+/// assert_eq!(result.centroids, [[30.0, 0.0], [0.0, 100.0]]);
+///
+/// // Range cuts the red channel, which spans the most
+/// let result = get_median_cut(2, BoxSplit::Range, None, &buf);
+/// assert_eq!(result.indices, [0, 0, 0, 0, 1, 1, 1]);
+///
+/// // Weights move the median like repeated points of a histogram
+/// let buf = [[0.0; 3], [10.0; 3], [20.0; 3], [30.0; 3]];
+/// let result = get_median_cut(2, BoxSplit::Variance, None, &buf);
+/// assert_eq!(result.centroids, [[5.0; 3], [25.0; 3]]);
+/// let result = get_median_cut(2, BoxSplit::Variance, Some(&[1.0, 1.0, 1.0, 5.0]), &buf);
+/// assert_eq!(result.centroids, [[10.0; 3], [30.0; 3]]);
+/// assert_eq!(result.score, 600.0);
+///
+/// // The boxes cut at the median, k-means refines them at the gap
+/// let buf = [[0.0; 3], [1.0; 3], [2.0; 3], [200.0; 3], [201.0; 3], [202.0; 3], [255.0; 3]];
+/// let seeds = get_median_cut(2, BoxSplit::Variance, None, &buf);
+/// assert_eq!(seeds.indices, [0, 0, 0, 0, 1, 1, 1]);
+/// let refined = get_kmeans_seeded(seeds.centroids, 20, 0.0, false, &buf);
+/// assert_eq!(refined.centroids, [[1.0; 3], [214.5; 3]]);
+/// assert_eq!(refined.indices, [0, 0, 0, 1, 1, 1, 1]);
+/// ```
+///
+/// ## Reference
+///
+/// Heckbert, P. (1982). Color image quantization for frame buffer display.
+/// ACM SIGGRAPH Computer Graphics, 16(3), 297-307.
+pub fn get_median_cut<const N: usize>(
+    k: usize,
+    split: BoxSplit,
+    weights: Option<&[f32]>,
+    buf: &[[f32; N]],
+) -> Kmeans<[f32; N]> {
+    assert!(!buf.is_empty());
+    assert!(k > 0);
+    let weights: Vec<f32> = match weights {
+        Some(weights) => {
+            assert_eq!(weights.len(), buf.len());
+            weights.to_vec()
+        }
+        None => vec![1.0; buf.len()],
+    };
+
+    let mut boxes: Vec<ColorBox<N>> = vec![ColorBox::new(
+        (0..buf.len()).collect(),
+        buf,
+        &weights,
+        split,
+    )];
+    while boxes.len() < k {
+        let chosen: Option<usize> = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.priority > 0.0)
+            .max_by(|(_, a), (_, b)| {
+                a.priority
+                    .partial_cmp(&b.priority)
+                    .unwrap_or(Ordering::Equal)
+            })
+            .map(|(i, _)| i);
+        let mut color_box: ColorBox<N> = match chosen {
+            Some(i) => boxes.swap_remove(i),
+            None => break,
+        };
+
+        // Cut at the weighted median, keeping both halves non-empty
+        let axis: usize = color_box.axis;
+        color_box.members.sort_by(|&a: &usize, &b: &usize| {
+            buf[a][axis]
+                .partial_cmp(&buf[b][axis])
+                .unwrap_or(Ordering::Equal)
+        });
+        let total: f32 = color_box.members.iter().map(|&i: &usize| weights[i]).sum();
+        let mut cumulative: f32 = 0.0;
+        let median: usize = color_box
+            .members
+            .iter()
+            .position(|&i: &usize| {
+                cumulative += weights[i];
+                cumulative >= total / 2.0
+            })
+            .unwrap_or(0);
+        let at: usize = (median + 1).clamp(1, color_box.members.len() - 1);
+
+        let upper: Vec<usize> = color_box.members.split_off(at);
+        boxes.push(ColorBox::new(color_box.members, buf, &weights, split));
+        boxes.push(ColorBox::new(upper, buf, &weights, split));
+    }
+
+    let mut indices: Vec<u8> = vec![0; buf.len()];
+    boxes
+        .iter()
+        .enumerate()
+        .for_each(|(label, b): (usize, &ColorBox<N>)| {
+            b.members
+                .iter()
+                .for_each(|&i: &usize| indices[i] = label as u8);
+        });
+
+    Kmeans {
+        score: boxes.par_iter().map(|b: &ColorBox<N>| b.sse).sum(),
+        centroids: boxes.iter().map(|b: &ColorBox<N>| b.mean).collect(),
+        indices,
+        locked: Vec::new(),
+    }
+}
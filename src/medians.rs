@@ -0,0 +1,112 @@
+use std::cmp::Ordering;
+
+use rand::{rngs::SmallRng, thread_rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::kmeans::{get_closest_centroid_with_metric, lloyd, Calculate, Kmeans};
+use crate::metric::Manhattan;
+
+/// Find the median of a list of values. For an even number of values, the
+/// mean of the two middle values is returned.
+fn median(values: &mut [f32]) -> f32 {
+    let len: usize = values.len();
+    let mid: usize = len / 2;
+    let (lower, upper, _) = values.select_nth_unstable_by(mid, |a: &f32, b: &f32| {
+        a.partial_cmp(b).unwrap_or(Ordering::Equal)
+    });
+    if lower.is_empty() || len % 2 == 1 {
+        *upper
+    } else {
+        let below: f32 = lower.iter().fold(f32::MIN, |max: f32, &v: &f32| max.max(v));
+        (below + *upper) / 2.0
+    }
+}
+
+/// Find the new centroid locations based on the per-channel median of the
+/// points that correspond to the centroid. If no points correspond, the
+/// centroid is re-initialized with a random point.
+///
+/// ```
+/// use kmeans_colors::recalculate_medians;
+///
+/// let buf = [[0.0, 4.0], [10.0, 3.0], [20.0, 2.0], [1000.0, 1.0], [5.0, 0.0], [7.0, 0.0]];
+/// let mut centroids = [[0.0; 2]; 2];
+/// recalculate_medians(&buf, &mut centroids, &[0, 0, 0, 0, 1, 1]);
+///
+/// // Even counts take the mean of the two middle values of each channel
+/// assert_eq!(centroids, [[15.0, 2.5], [6.0, 0.0]]);
+/// ```
+pub fn recalculate_medians<const N: usize>(
+    buf: &[[f32; N]],
+    centroids: &mut [[f32; N]],
+    indices: &[u8],
+) {
+    centroids
+        .par_iter_mut()
+        .enumerate()
+        .for_each(|(i, centroid): (usize, &mut [f32; N])| {
+            let members: Vec<&[f32; N]> = indices
+                .iter()
+                .zip(buf.iter())
+                .filter(|(&index, _)| index == i as u8)
+                .map(|(_, color)| color)
+                .collect();
+
+            if members.is_empty() {
+                *centroid = <[f32; N]>::create_random(&mut thread_rng());
+                return;
+            }
+
+            let mut channel: Vec<f32> = Vec::with_capacity(members.len());
+            (0..N).for_each(|c: usize| {
+                channel.clear();
+                channel.extend(members.iter().map(|color: &&[f32; N]| color[c]));
+                centroid[c] = median(&mut channel);
+            });
+        });
+}
+
+/// Find the k-medians centroids of a buffer. Takes the same arguments as
+/// [`get_kmeans`](fn.get_kmeans.html).
+///
+/// Points are assigned with the [`Manhattan`](struct.Manhattan.html) distance
+/// and centroids are recalculated as the per-channel median of their points.
+/// Medians are robust to a small number of extreme points, such as specular
+/// highlights or compression noise, which would drag the mean of a cluster.
+/// Initialization uses k-means++ with the Manhattan distance.
+///
+/// ```
+/// use kmeans_colors::get_kmedians;
+///
+/// let buf = [[0.0; 3], [1.0; 3], [2.0; 3], [200.0; 3], [201.0; 3], [202.0; 3], [255.0; 3]];
+/// let mut result = get_kmedians(2, 20, 0.0, false, &buf);
+///
+/// // The extreme point would drag the mean to 214.5 but not the median
+/// result.centroids.sort_by(|a, b| a[0].total_cmp(&b[0]));
+/// assert_eq!(result.centroids, [[1.0; 3], [201.5; 3]]);
+/// ```
+pub fn get_kmedians<const N: usize>(
+    k: usize,
+    max_iter: usize,
+    converge: f32,
+    verbose: bool,
+    buf: &[[f32; N]],
+) -> Kmeans<[f32; N]> {
+    // Initialize the random centroids
+    let mut rng: SmallRng = SmallRng::from_rng(thread_rng()).unwrap();
+    let mut centroids: Vec<[f32; N]> = Vec::with_capacity(k);
+    crate::plus_plus::init_plus_plus_with_metric(k, &mut rng, buf, &mut centroids, &Manhattan);
+
+    lloyd(
+        centroids,
+        max_iter,
+        converge,
+        verbose,
+        buf,
+        &Manhattan,
+        |buf: &[[f32; N]], centroids: &[[f32; N]], indices: &mut Vec<u8>| {
+            get_closest_centroid_with_metric(buf, centroids, indices, &Manhattan)
+        },
+        recalculate_medians,
+    )
+}
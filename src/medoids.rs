@@ -0,0 +1,341 @@
+use rand::{rngs::SmallRng, seq::index::sample, thread_rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::kmeans::{Calculate, Kmeans};
+
+/// Cached nearest and second nearest medoids of a point.
+#[derive(Copy, Clone, Debug)]
+struct Nearest {
+    /// Slot of the nearest medoid.
+    near: usize,
+    /// Difference to the nearest medoid.
+    dist_near: f32,
+    /// Difference to the second nearest medoid.
+    dist_second: f32,
+}
+
+/// Find the nearest and second nearest medoids of every point.
+fn compute_nearest<C: Calculate + Sync>(buf: &[C], medoids: &[usize]) -> Vec<Nearest> {
+    buf.par_iter()
+        .map(|point: &C| {
+            let mut nearest: Nearest = Nearest {
+                near: 0,
+                dist_near: f32::MAX,
+                dist_second: f32::MAX,
+            };
+            medoids
+                .iter()
+                .enumerate()
+                .for_each(|(slot, &m): (usize, &usize)| {
+                    let diff: f32 = C::difference(point, &buf[m]);
+                    if diff < nearest.dist_near {
+                        nearest.dist_second = nearest.dist_near;
+                        nearest.dist_near = diff;
+                        nearest.near = slot;
+                    } else if diff < nearest.dist_second {
+                        nearest.dist_second = diff;
+                    }
+                });
+            nearest
+        })
+        .collect()
+}
+
+/// Loss in total deviation from removing each medoid.
+fn removal_loss(nearest: &[Nearest], k: usize) -> Vec<f32> {
+    let mut loss: Vec<f32> = vec![0.0; k];
+    nearest.iter().for_each(|n: &Nearest| {
+        loss[n.near] += n.dist_second - n.dist_near;
+    });
+    loss
+}
+
+/// Greedy BUILD initialization, returns the positions of `k` medoids.
+fn build<C: Calculate + Sync>(k: usize, buf: &[C]) -> Vec<usize> {
+    let mut medoids: Vec<usize> = Vec::with_capacity(k);
+
+    // The first medoid minimizes the total deviation of the buffer
+    let first: usize = (0..buf.len())
+        .into_par_iter()
+        .map(|i: usize| {
+            let total: f32 = buf.iter().map(|p: &C| C::difference(&buf[i], p)).sum();
+            (i, total)
+        })
+        .reduce(
+            || (0, f32::MAX),
+            |a: (usize, f32), b: (usize, f32)| if b.1 < a.1 { b } else { a },
+        )
+        .0;
+    medoids.push(first);
+    let mut dist_near: Vec<f32> = buf
+        .par_iter()
+        .map(|p: &C| C::difference(p, &buf[first]))
+        .collect();
+
+    // Each following medoid is the point which reduces the deviation the most
+    while medoids.len() < k {
+        let best: usize = (0..buf.len())
+            .into_par_iter()
+            .filter(|i: &usize| !medoids.contains(i))
+            .map(|i: usize| {
+                let gain: f32 = buf
+                    .iter()
+                    .zip(dist_near.iter())
+                    .map(|(p, &d): (&C, &f32)| (d - C::difference(p, &buf[i])).max(0.0))
+                    .sum();
+                (i, gain)
+            })
+            .reduce(
+                || (usize::MAX, f32::MIN),
+                |a: (usize, f32), b: (usize, f32)| if b.1 > a.1 { b } else { a },
+            )
+            .0;
+        if best == usize::MAX {
+            break;
+        }
+        medoids.push(best);
+        dist_near
+            .par_iter_mut()
+            .zip(buf.par_iter())
+            .for_each(|(d, p): (&mut f32, &C)| *d = d.min(C::difference(p, &buf[best])));
+    }
+
+    medoids
+}
+
+/// Run BUILD followed by FasterPAM swaps, returns the medoid positions and the
+/// nearest medoid cache.
+fn pam<C: Calculate + Sync>(
+    k: usize,
+    max_iter: usize,
+    verbose: bool,
+    buf: &[C],
+) -> (Vec<usize>, Vec<Nearest>) {
+    let mut medoids: Vec<usize> = build(k, buf);
+    let mut nearest: Vec<Nearest> = compute_nearest(buf, &medoids);
+
+    // With a single medoid BUILD is already optimal, and with every point as a
+    // medoid there is nothing to swap
+    if medoids.len() < 2 || medoids.len() >= buf.len() {
+        return (medoids, nearest);
+    }
+
+    let mut loss: Vec<f32> = removal_loss(&nearest, medoids.len());
+    let mut last_swap: usize = 0;
+    let mut iterations: usize = 0;
+
+    'outer: while iterations < max_iter {
+        for candidate in 0..buf.len() {
+            if candidate == last_swap && iterations > 0 {
+                break 'outer;
+            }
+            if medoids.contains(&candidate) {
+                continue;
+            }
+
+            // Change in deviation from adding the candidate, per removed medoid
+            let (delta, acc): (Vec<f32>, f32) = buf
+                .par_iter()
+                .zip(nearest.par_iter())
+                .fold(
+                    || (vec![0.0; loss.len()], 0.0),
+                    |(mut delta, mut acc): (Vec<f32>, f32), (p, n): (&C, &Nearest)| {
+                        let diff: f32 = C::difference(p, &buf[candidate]);
+                        if diff < n.dist_near {
+                            acc += diff - n.dist_near;
+                            delta[n.near] += n.dist_near - n.dist_second;
+                        } else if diff < n.dist_second {
+                            delta[n.near] += diff - n.dist_second;
+                        }
+                        (delta, acc)
+                    },
+                )
+                .reduce(
+                    || (vec![0.0; loss.len()], 0.0),
+                    |(mut d1, a1): (Vec<f32>, f32), (d2, a2): (Vec<f32>, f32)| {
+                        d1.iter_mut()
+                            .zip(d2.iter())
+                            .for_each(|(a, b): (&mut f32, &f32)| *a += b);
+                        (d1, a1 + a2)
+                    },
+                );
+
+            // Choose the medoid whose replacement reduces the deviation most
+            let (slot, change): (usize, f32) = delta
+                .iter()
+                .zip(loss.iter())
+                .map(|(d, l): (&f32, &f32)| d + l + acc)
+                .enumerate()
+                .fold(
+                    (0, f32::MAX),
+                    |a: (usize, f32), b: (usize, f32)| {
+                        if b.1 < a.1 {
+                            b
+                        } else {
+                            a
+                        }
+                    },
+                );
+
+            if change < 0.0 {
+                if verbose {
+                    println!(
+                        "Swap: {} -> {}, change: {}",
+                        medoids[slot], candidate, change
+                    );
+                }
+                medoids[slot] = candidate;
+                nearest = compute_nearest(buf, &medoids);
+                loss = removal_loss(&nearest, medoids.len());
+                last_swap = candidate;
+            }
+        }
+        iterations += 1;
+        if verbose {
+            println!("Iterations: {}", iterations);
+        }
+    }
+
+    (medoids, nearest)
+}
+
+/// Find the k-medoids of a buffer with the PAM BUILD initialization and the
+/// FasterPAM swap phase.
+///
+/// Unlike k-means, the centroids of the result are points of the input buffer
+/// and the clustering minimizes the sum of
+/// [`Calculate::difference`](trait.Calculate.html#tymethod.difference) to the
+/// nearest medoid, so any dissimilarity can be used. The `score` of the result
+/// is this total deviation.
+///
+/// - `k` - number of clusters.
+/// - `max_iter` - maximum number of passes over the buffer in the swap phase.
+/// - `verbose` - flag for printing swap information to console.
+/// - `buf` - array of points.
+///
+/// Each pass is quadratic in the buffer length, see
+/// [`get_kmedoids_clara`](fn.get_kmedoids_clara.html) for large buffers.
+///
+/// ```
+/// use kmeans_colors::get_kmedoids;
+///
+/// let buf = [[0.0; 3], [1.0; 3], [3.0; 3], [100.0; 3], [101.0; 3], [103.0; 3]];
+/// let mut result = get_kmedoids(2, 10, false, &buf);
+///
+/// // The medoids are the points closest to the rest of their cluster
+/// result.centroids.sort_by(|a, b| a[0].total_cmp(&b[0]));
+/// assert_eq!(result.centroids, [[1.0; 3], [101.0; 3]]);
+/// assert_eq!(result.score, 30.0);
+/// assert_eq!(result.indices[..3], [result.indices[0]; 3]);
+/// assert_eq!(result.indices[3..], [result.indices[3]; 3]);
+/// assert_ne!(result.indices[0], result.indices[3]);
+/// ```
+///
+/// ## Reference
+///
+/// Schubert, E., & Rousseeuw, P. J. (2021). Fast and eager k-medoids
+/// clustering: O(k) runtime improvement of the PAM, CLARA, and CLARANS
+/// algorithms. Information Systems, 101, 101804.
+pub fn get_kmedoids<C: Calculate + Clone + Sync + Send>(
+    k: usize,
+    max_iter: usize,
+    verbose: bool,
+    buf: &[C],
+) -> Kmeans<C> {
+    if k == 0 || buf.is_empty() {
+        return Kmeans::new();
+    }
+
+    let (medoids, nearest): (Vec<usize>, Vec<Nearest>) = pam(k, max_iter, verbose, buf);
+
+    Kmeans {
+        score: nearest.iter().map(|n: &Nearest| n.dist_near).sum(),
+        centroids: medoids.iter().map(|&m: &usize| buf[m].clone()).collect(),
+        indices: nearest.iter().map(|n: &Nearest| n.near as u8).collect(),
+        locked: Vec::new(),
+    }
+}
+
+/// Find the k-medoids of a large buffer with CLARA, running
+/// [`get_kmedoids`](fn.get_kmedoids.html) on random samples of the buffer.
+///
+/// Each of the `samples` runs clusters `sample_size` randomly chosen points.
+/// The medoids with the lowest total deviation over the full buffer are kept
+/// and every point of the buffer is indexed to its nearest medoid. A sample
+/// size of `40 + 2 * k` is suggested by the reference.
+///
+/// ```
+/// use kmeans_colors::get_kmedoids_clara;
+///
+/// // Two tight groups of 50 points
+/// let buf: Vec<[f32; 3]> = (0..100)
+///     .map(|i| [(i / 50) as f32 * 200.0 + (i % 5) as f32; 3])
+///     .collect();
+/// let result = get_kmedoids_clara(2, 10, false, &buf, 5, 20);
+///
+/// // Every point is indexed with the medoid of its group
+/// assert_eq!(result.indices[..50], [result.indices[0]; 50]);
+/// assert_eq!(result.indices[50..], [result.indices[50]; 50]);
+/// assert_ne!(result.indices[0], result.indices[50]);
+/// assert!(result.centroids.iter().all(|c| buf.contains(c)));
+/// ```
+///
+/// ## Reference
+///
+/// Kaufman, L., & Rousseeuw, P. J. (1990). Clustering Large Applications
+/// (Program CLARA). In: Finding Groups in Data.
+pub fn get_kmedoids_clara<C: Calculate + Clone + Sync + Send>(
+    k: usize,
+    max_iter: usize,
+    verbose: bool,
+    buf: &[C],
+    samples: usize,
+    sample_size: usize,
+) -> Kmeans<C> {
+    if sample_size >= buf.len() {
+        return get_kmedoids(k, max_iter, verbose, buf);
+    }
+
+    let mut rng: SmallRng = SmallRng::from_rng(thread_rng()).unwrap();
+    let mut result: Kmeans<C> = Kmeans::new();
+
+    (0..samples).for_each(|_| {
+        let subset: Vec<C> = sample(&mut rng, buf.len(), sample_size)
+            .iter()
+            .map(|i: usize| buf[i].clone())
+            .collect();
+        let run_result: Kmeans<C> = get_kmedoids(k, max_iter, verbose, &subset);
+
+        let nearest: Vec<(u8, f32)> = buf
+            .par_iter()
+            .map(|p: &C| {
+                run_result.centroids.iter().enumerate().fold(
+                    (0, f32::MAX),
+                    |(idx, min): (u8, f32), (j, c): (usize, &C)| {
+                        let diff: f32 = C::difference(p, c);
+                        if diff < min {
+                            (j as u8, diff)
+                        } else {
+                            (idx, min)
+                        }
+                    },
+                )
+            })
+            .collect();
+        let score: f32 = nearest.iter().map(|&(_, d): &(u8, f32)| d).sum();
+
+        if verbose {
+            println!("Sample score: {}", score);
+        }
+        if score < result.score {
+            result = Kmeans {
+                score,
+                centroids: run_result.centroids,
+                indices: nearest.iter().map(|&(i, _): &(u8, f32)| i).collect(),
+                locked: Vec::new(),
+            };
+        }
+    });
+
+    result
+}
@@ -0,0 +1,178 @@
+/// A trait for the distance metric used to compare points during clustering.
+///
+/// The metric is decoupled from the point type so the same buffer can be
+/// clustered under different notions of similarity without reimplementing
+/// [`Calculate`](trait.Calculate.html). Closures of the form
+/// `Fn(&C, &C) -> f32` implement `Metric<C>` and can be used for user-defined
+/// metrics.
+pub trait Metric<C>: Sync + Send {
+    /// Calculate the dissimilarity between two points. Points are assigned to
+    /// the centroid with the smallest difference.
+    fn difference(&self, c1: &C, c2: &C) -> f32;
+
+    /// Calculate a distance between two points that orders points the same way
+    /// as [`difference`](#tymethod.difference). If
+    /// [`triangle_inequality`](#method.triangle_inequality) returns `true`,
+    /// this distance must satisfy the triangle inequality.
+    #[inline]
+    fn distance(&self, c1: &C, c2: &C) -> f32 {
+        self.difference(c1, c2)
+    }
+
+    /// Whether [`distance`](#method.distance) satisfies the triangle
+    /// inequality. Bound-based accelerations like the Hamerly algorithm are
+    /// only used when this returns `true`.
+    #[inline]
+    fn triangle_inequality(&self) -> bool {
+        false
+    }
+}
+
+impl<C, F> Metric<C> for F
+where
+    F: Fn(&C, &C) -> f32 + Sync + Send,
+{
+    #[inline]
+    fn difference(&self, c1: &C, c2: &C) -> f32 {
+        self(c1, c2)
+    }
+}
+
+/// Squared Euclidean distance, the metric minimized by standard k-means.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SquaredEuclidean;
+
+impl<const N: usize> Metric<[f32; N]> for SquaredEuclidean {
+    #[inline]
+    fn difference(&self, c1: &[f32; N], c2: &[f32; N]) -> f32 {
+        c1.iter()
+            .zip(c2.iter())
+            .map(|(a, b): (&f32, &f32)| (a - b) * (a - b))
+            .sum()
+    }
+
+    #[inline]
+    fn distance(&self, c1: &[f32; N], c2: &[f32; N]) -> f32 {
+        self.difference(c1, c2).sqrt()
+    }
+
+    #[inline]
+    fn triangle_inequality(&self) -> bool {
+        true
+    }
+}
+
+/// Manhattan (L1) distance, the sum of absolute differences of each channel.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Manhattan;
+
+impl<const N: usize> Metric<[f32; N]> for Manhattan {
+    #[inline]
+    fn difference(&self, c1: &[f32; N], c2: &[f32; N]) -> f32 {
+        c1.iter()
+            .zip(c2.iter())
+            .map(|(a, b): (&f32, &f32)| (a - b).abs())
+            .sum()
+    }
+
+    #[inline]
+    fn triangle_inequality(&self) -> bool {
+        true
+    }
+}
+
+/// Cosine distance, `1 - cos(θ)` where `θ` is the angle between two points.
+///
+/// A point with zero magnitude is treated as being orthogonal to every other
+/// point.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Cosine;
+
+impl<const N: usize> Metric<[f32; N]> for Cosine {
+    #[inline]
+    fn difference(&self, c1: &[f32; N], c2: &[f32; N]) -> f32 {
+        let (dot, norm1, norm2): (f32, f32, f32) = c1.iter().zip(c2.iter()).fold(
+            (0.0, 0.0, 0.0),
+            |(dot, n1, n2): (f32, f32, f32), (a, b): (&f32, &f32)| {
+                (dot + a * b, n1 + a * a, n2 + b * b)
+            },
+        );
+        let norm: f32 = (norm1 * norm2).sqrt();
+        if norm > 0.0 {
+            1.0 - dot / norm
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Euclidean distance with a weight applied to each channel.
+///
+/// Weights can be used to tune how much each channel contributes to the
+/// clustering, e.g. emphasizing chroma over lightness for `Lab` points. Since
+/// the weights are applied by the metric, the buffer does not need to be
+/// preprocessed. The triangle inequality only holds when all weights are
+/// non-negative.
+///
+/// The weights are used by the functions taking a metric:
+/// [`get_kmeans_with_metric`](fn.get_kmeans_with_metric.html),
+/// [`get_kmeans_hamerly_with_metric`](fn.get_kmeans_hamerly_with_metric.html),
+/// whose bounds are computed from the weighted distance, and
+/// [`init_plus_plus_with_metric`](fn.init_plus_plus_with_metric.html).
+/// `Calculate::difference` and the functions without a metric are unweighted.
+///
+/// ```
+/// use kmeans_colors::{get_kmeans_hamerly_with_metric, WeightedEuclidean};
+///
+/// let rgb = [[0.0, 0.0, 0.0], [255.0, 255.0, 255.0]];
+/// let result = get_kmeans_hamerly_with_metric(2, 20, 0.0, false, &rgb, &WeightedEuclidean::REC601);
+/// assert_ne!(result.indices[0], result.indices[1]);
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct WeightedEuclidean<const N: usize> {
+    /// Weight multiplied with the squared difference of each channel.
+    pub weights: [f32; N],
+}
+
+impl<const N: usize> WeightedEuclidean<N> {
+    /// Create a new `WeightedEuclidean` metric from per-channel weights.
+    pub const fn new(weights: [f32; N]) -> Self {
+        WeightedEuclidean { weights }
+    }
+}
+
+impl WeightedEuclidean<3> {
+    /// Rec. 601 luma weights `[0.299, 0.587, 0.114]` for `[r, g, b]` points.
+    pub const REC601: Self = WeightedEuclidean::new([0.299, 0.587, 0.114]);
+
+    /// Rec. 709 luma weights `[0.2126, 0.7152, 0.0722]` for `[r, g, b]` points.
+    pub const REC709: Self = WeightedEuclidean::new([0.2126, 0.7152, 0.0722]);
+
+    /// Weights for `[l, a, b]` points, applying `lightness` to the `L` channel
+    /// and `chroma` to the `a` and `b` channels. A `lightness` lower than
+    /// `chroma` favors hue fidelity in the resulting palette.
+    pub const fn lab(lightness: f32, chroma: f32) -> Self {
+        WeightedEuclidean::new([lightness, chroma, chroma])
+    }
+}
+
+impl<const N: usize> Metric<[f32; N]> for WeightedEuclidean<N> {
+    #[inline]
+    fn difference(&self, c1: &[f32; N], c2: &[f32; N]) -> f32 {
+        c1.iter()
+            .zip(c2.iter())
+            .zip(self.weights.iter())
+            .map(|((a, b), w): ((&f32, &f32), &f32)| w * (a - b) * (a - b))
+            .sum()
+    }
+
+    #[inline]
+    fn distance(&self, c1: &[f32; N], c2: &[f32; N]) -> f32 {
+        self.difference(c1, c2).sqrt()
+    }
+
+    #[inline]
+    fn triangle_inequality(&self) -> bool {
+        self.weights.iter().all(|w: &f32| *w >= 0.0)
+    }
+}
@@ -0,0 +1,312 @@
+use rayon::prelude::*;
+
+use crate::colors::nearest;
+use crate::kmeans::Kmeans;
+
+/// A node of the octree, holding the totals of every color inserted below it.
+#[derive(Clone, Debug)]
+struct Node {
+    children: [Option<usize>; 8],
+    leaf: bool,
+    count: u64,
+    sum: [f64; 3],
+    squares: f64,
+}
+
+impl Node {
+    fn new(leaf: bool) -> Self {
+        Node {
+            children: [None; 8],
+            leaf,
+            count: 0,
+            sum: [0.0; 3],
+            squares: 0.0,
+        }
+    }
+
+    fn mean(&self) -> [f32; 3] {
+        self.sum.map(|s: f64| (s / self.count.max(1) as f64) as f32)
+    }
+}
+
+/// Octree color quantizer built incrementally as colors arrive.
+///
+/// Each color in the range `0.0..=255.0` descends the tree by one bit of its
+/// red, green, and blue channels per level, down to a leaf at `depth` levels.
+/// Leaves accumulate the colors reaching them. Whenever there are more than
+/// `max_colors` leaves, the node with the fewest colors among the deepest
+/// nodes with leaf children is reduced to a single leaf, merging its children.
+/// Memory stays bounded by the number of leaves rather than the number of
+/// colors, so buffers can be inserted in chunks as they are read.
+///
+/// The palette is the mean color of each leaf.
+/// [`finalize`](#method.finalize) produces a `Kmeans` result like the other
+/// quantizers and [`label`](#method.label) indexes the chunks in a second
+/// pass.
+///
+/// ```
+/// use kmeans_colors::{MapColor, Octree};
+///
+/// let chunks = [
+///     [[0.0, 0.0, 0.0], [255.0, 255.0, 255.0]],
+///     [[2.0, 2.0, 2.0], [253.0, 253.0, 253.0]],
+/// ];
+/// let mut octree = Octree::new(2, 8);
+/// chunks.iter().for_each(|chunk| octree.insert(chunk));
+///
+/// let result = octree.finalize();
+/// assert!(result.indices.is_empty());
+/// let indices = octree.label(&chunks[1]);
+/// let colors = <[f32; 3]>::map_indices_to_centroids(&result.centroids, &indices);
+/// assert_eq!(colors, [[1.0; 3], [254.0; 3]]);
+///
+/// // An empty octree has no palette to index with
+/// assert!(Octree::new(2, 8).label(&chunks[0]).is_empty());
+/// ```
+///
+/// ## Reference
+///
+/// Gervautz, M., & Purgathofer, W. (1988). A simple method for color
+/// quantization: Octree quantization. In: New Trends in Computer Graphics.
+#[derive(Clone, Debug)]
+pub struct Octree {
+    max_colors: usize,
+    depth: usize,
+    nodes: Vec<Node>,
+    /// Indices of reduced nodes available for reuse.
+    free: Vec<usize>,
+    /// Nodes with children at each level.
+    reducible: Vec<Vec<usize>>,
+    leaves: usize,
+}
+
+impl Octree {
+    /// Create a new `Octree` with at most `max_colors` leaves and `depth`
+    /// levels below the root.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_colors` is not in the range `1..=256` or `depth` is not
+    /// in the range `1..=8`.
+    pub fn new(max_colors: usize, depth: usize) -> Self {
+        assert!((1..=256).contains(&max_colors));
+        assert!((1..=8).contains(&depth));
+
+        // The root is the only node at the first level
+        let mut reducible: Vec<Vec<usize>> = vec![Vec::new(); depth];
+        reducible[0].push(0);
+
+        Octree {
+            max_colors,
+            depth,
+            nodes: vec![Node::new(false)],
+            free: Vec::new(),
+            reducible,
+            leaves: 0,
+        }
+    }
+
+    /// Maximum number of leaves.
+    pub fn max_colors(&self) -> usize {
+        self.max_colors
+    }
+
+    /// Number of levels below the root.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Current number of leaves.
+    pub fn leaf_count(&self) -> usize {
+        self.leaves
+    }
+
+    /// Index of the child of a color at a level.
+    #[inline]
+    fn octant(color: &[f32; 3], level: usize) -> usize {
+        let shift: usize = 7 - level;
+        color.iter().fold(0, |octant: usize, &c: &f32| {
+            let value: u8 = c.round().clamp(0.0, 255.0) as u8;
+            (octant << 1) | ((value >> shift) & 1) as usize
+        })
+    }
+
+    fn allocate(&mut self, leaf: bool) -> usize {
+        match self.free.pop() {
+            Some(i) => {
+                self.nodes[i] = Node::new(leaf);
+                i
+            }
+            None => {
+                self.nodes.push(Node::new(leaf));
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Insert a chunk of colors, reducing the tree to at most `max_colors`
+    /// leaves.
+    pub fn insert(&mut self, chunk: &[[f32; 3]]) {
+        chunk.iter().for_each(|color: &[f32; 3]| {
+            let squares: f64 = color.iter().map(|&c: &f32| (c as f64).powi(2)).sum();
+            let mut node: usize = 0;
+            let mut level: usize = 0;
+            loop {
+                let current: &mut Node = &mut self.nodes[node];
+                current.count += 1;
+                (0..3).for_each(|c: usize| current.sum[c] += color[c] as f64);
+                current.squares += squares;
+                if current.leaf {
+                    break;
+                }
+
+                let octant: usize = Self::octant(color, level);
+                node = match self.nodes[node].children[octant] {
+                    Some(child) => child,
+                    None => {
+                        let leaf: bool = level + 1 == self.depth;
+                        let child: usize = self.allocate(leaf);
+                        self.nodes[node].children[octant] = Some(child);
+                        if leaf {
+                            self.leaves += 1;
+                        } else {
+                            self.reducible[level + 1].push(child);
+                        }
+                        child
+                    }
+                };
+                level += 1;
+            }
+
+            while self.leaves > self.max_colors {
+                self.reduce();
+            }
+        });
+    }
+
+    /// Merge the children of the deepest node with the fewest colors into it.
+    fn reduce(&mut self) {
+        let level: usize = match self
+            .reducible
+            .iter()
+            .rposition(|l: &Vec<usize>| !l.is_empty())
+        {
+            Some(level) => level,
+            None => return,
+        };
+        let (position, _): (usize, &usize) = self.reducible[level]
+            .iter()
+            .enumerate()
+            .min_by_key(|&(_, &i): &(usize, &usize)| self.nodes[i].count)
+            .unwrap();
+        let node: usize = self.reducible[level].swap_remove(position);
+
+        let children: [Option<usize>; 8] = std::mem::take(&mut self.nodes[node].children);
+        children.iter().flatten().for_each(|&child: &usize| {
+            self.leaves -= 1;
+            self.free.push(child);
+        });
+        self.nodes[node].leaf = true;
+        self.leaves += 1;
+    }
+
+    /// Leaves in depth-first order.
+    fn leaf_nodes(&self) -> Vec<usize> {
+        let mut leaves: Vec<usize> = Vec::with_capacity(self.leaves);
+        let mut stack: Vec<usize> = vec![0];
+        while let Some(node) = stack.pop() {
+            if self.nodes[node].leaf {
+                leaves.push(node);
+            }
+            stack.extend(self.nodes[node].children.iter().rev().flatten());
+        }
+        leaves
+    }
+
+    /// Current palette, the mean color of each leaf.
+    pub fn palette(&self) -> Vec<[f32; 3]> {
+        self.leaf_nodes()
+            .iter()
+            .map(|&i: &usize| self.nodes[i].mean())
+            .collect()
+    }
+
+    /// Index each color of a chunk with the palette color of its leaf. Colors
+    /// without a leaf, which were never inserted, are indexed with their
+    /// nearest palette color. Returns an empty Vec if no colors have been
+    /// inserted.
+    pub fn label(&self, chunk: &[[f32; 3]]) -> Vec<u8> {
+        let leaves: Vec<usize> = self.leaf_nodes();
+        if leaves.is_empty() {
+            return Vec::new();
+        }
+        let palette: Vec<[f32; 3]> = leaves
+            .iter()
+            .map(|&i: &usize| self.nodes[i].mean())
+            .collect();
+        let mut slots: Vec<u8> = vec![0; self.nodes.len()];
+        leaves
+            .iter()
+            .enumerate()
+            .for_each(|(slot, &i): (usize, &usize)| slots[i] = slot as u8);
+
+        chunk
+            .par_iter()
+            .map(|color: &[f32; 3]| {
+                let mut node: usize = 0;
+                let mut level: usize = 0;
+                while !self.nodes[node].leaf {
+                    match self.nodes[node].children[Self::octant(color, level)] {
+                        Some(child) => node = child,
+                        None => return nearest(color, &palette),
+                    }
+                    level += 1;
+                }
+                slots[node]
+            })
+            .collect()
+    }
+
+    /// Produce a `Kmeans` result from the current palette. The `score` is the
+    /// total sum of squared errors of the leaves.
+    ///
+    /// Unlike the other quantizers, the `indices` of the result are empty
+    /// because the chunks are not kept. Use [`label`](#method.label) for a
+    /// second pass over the chunks.
+    pub fn finalize(&self) -> Kmeans<[f32; 3]> {
+        let leaves: Vec<usize> = self.leaf_nodes();
+        Kmeans {
+            score: leaves
+                .iter()
+                .map(|&i: &usize| {
+                    let node: &Node = &self.nodes[i];
+                    let norm: f64 = node.sum.iter().map(|s: &f64| s * s).sum::<f64>();
+                    (node.squares - norm / node.count.max(1) as f64).max(0.0)
+                })
+                .sum::<f64>() as f32,
+            centroids: leaves
+                .iter()
+                .map(|&i: &usize| self.nodes[i].mean())
+                .collect(),
+            indices: Vec::new(),
+            locked: Vec::new(),
+        }
+    }
+}
+
+/// Quantize a buffer of colors in the range `0.0..=255.0` into at most
+/// `max_colors` colors with an [`Octree`](struct.Octree.html) of `depth`
+/// levels, indexing each color with its palette color.
+///
+/// # Panics
+///
+/// Panics if `max_colors` is not in the range `1..=256` or `depth` is not in
+/// the range `1..=8`.
+pub fn get_octree(max_colors: usize, depth: usize, buf: &[[f32; 3]]) -> Kmeans<[f32; 3]> {
+    let mut octree: Octree = Octree::new(max_colors, depth);
+    octree.insert(buf);
+
+    let mut result: Kmeans<[f32; 3]> = octree.finalize();
+    result.indices = octree.label(buf);
+    result
+}
@@ -2,7 +2,9 @@ use rand::distributions::{Distribution, WeightedIndex};
 use rand::Rng;
 use rayon::prelude::*;
 
-const F32_MAX: f32 = core::f32::MAX;
+use crate::metric::Metric;
+
+const F32_MAX: f32 = f32::MAX;
 
 /// k-means++ centroid initialization.
 ///
@@ -15,10 +17,50 @@ const F32_MAX: f32 = core::f32::MAX;
 /// Based on Section 2.2 from `k-means++: The Advantages of Careful Seeding` by
 /// Arthur and Vassilvitskii (2007).
 pub fn init_plus_plus<C: crate::Calculate + Clone + Sync + Send>(
+    k: usize,
+    rng: &mut impl Rng,
+    buf: &[C],
+    centroids: &mut Vec<C>,
+) {
+    init_plus_plus_with_metric(k, rng, buf, centroids, &|c1: &C, c2: &C| {
+        C::difference(c1, c2)
+    });
+}
+
+/// k-means++ centroid initialization using a custom distance
+/// [`Metric`](trait.Metric.html). The `D(x)` weighting is taken from the
+/// metric's `difference`.
+///
+/// # Panics
+///
+/// Panics if buffer is empty.
+pub fn init_plus_plus_with_metric<C: Clone + Sync + Send, M: Metric<C>>(
+    k: usize,
+    rng: &mut impl Rng,
+    buf: &[C],
+    centroids: &mut Vec<C>,
+    metric: &M,
+) {
+    if k == 0 {
+        return;
+    }
+    assert!(!buf.is_empty());
+
+    // Choose first centroid at random, uniform sampling from input buffer
+    let total: usize = centroids.len() + k;
+    centroids.push(buf.get(rng.gen_range(0..buf.len())).unwrap().to_owned());
+    extend_plus_plus(total, rng, buf, centroids, metric);
+}
+
+/// k-means++ initialization continuing from the centroids already in
+/// `centroids`, which count towards `k`. The first centroid is chosen at
+/// random only if `centroids` is empty.
+pub(crate) fn extend_plus_plus<C: Clone + Sync + Send, M: Metric<C>>(
     k: usize,
     mut rng: &mut impl Rng,
     buf: &[C],
     centroids: &mut Vec<C>,
+    metric: &M,
 ) {
     if k == 0 {
         return;
@@ -29,11 +71,13 @@ pub fn init_plus_plus<C: crate::Calculate + Clone + Sync + Send>(
     let mut weights: Vec<f32> = vec![0.0; buf_len];
 
     // Choose first centroid at random, uniform sampling from input buffer
-    centroids.push(buf.get(rng.gen_range(0..buf_len)).unwrap().to_owned());
+    if centroids.is_empty() {
+        centroids.push(buf.get(rng.gen_range(0..buf_len)).unwrap().to_owned());
+    }
 
     // Pick a new centroid with weighted probability of `D(x)^2 / sum(D(x)^2)`,
     // where `D(x)^2` is the distance to the closest centroid
-    (1..k).for_each(|_| {
+    (centroids.len()..k).for_each(|_| {
         // Calculate the distances to nearest centers, accumulate a sum
         let mut sum: f32 = 0.0;
         weights
@@ -42,7 +86,7 @@ pub fn init_plus_plus<C: crate::Calculate + Clone + Sync + Send>(
             .for_each(|(idx, weight): (usize, &mut f32)| {
                 let mut min: f32 = F32_MAX;
                 centroids.iter().for_each(|cent: &C| {
-                    let diff: f32 = C::difference(&buf[idx], cent);
+                    let diff: f32 = metric.difference(&buf[idx], cent);
                     if diff < min {
                         min = diff;
                     }
@@ -0,0 +1,454 @@
+use std::ops::RangeInclusive;
+
+use rand::{rngs::SmallRng, thread_rng, Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::kmeans::{get_closest_centroid_with_metric, get_kmeans, lloyd, Calculate, Kmeans};
+use crate::metric::SquaredEuclidean;
+
+/// Criterion used to select the number of clusters.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Criterion {
+    /// Choose the `k` at the elbow of the inertia curve, the point furthest
+    /// below the line between the first and last inertia. Scores are the
+    /// inertia of each `k`.
+    Elbow,
+    /// Choose the `k` with the highest mean silhouette. The silhouette is
+    /// computed on about `sample_size` evenly spaced points. Scores are the
+    /// mean silhouette of each `k`.
+    Silhouette {
+        /// Number of points used to calculate the silhouette.
+        sample_size: usize,
+    },
+    /// Choose the smallest `k` whose gap statistic is within one standard
+    /// error of the gap of `k + 1`. The gap compares the inertia to that of
+    /// `references` uniformly random buffers spanning the bounding box of the
+    /// input. Scores are the gap of each `k`.
+    Gap {
+        /// Number of reference buffers generated for each `k`.
+        references: usize,
+    },
+    /// X-means, starting from the smallest `k` and splitting each cluster in
+    /// two when the split improves the Bayesian information criterion. Scores
+    /// are the BIC of each `k` visited, higher is better.
+    XMeans,
+}
+
+/// Struct result of selecting the number of clusters.
+#[derive(Clone, Debug)]
+pub struct Selection<C: Calculate> {
+    /// The selected number of clusters.
+    pub k: usize,
+    /// k-means result of the selected `k`.
+    pub result: Kmeans<C>,
+    /// Score of each `k` that was evaluated, as `(k, score)`.
+    pub scores: Vec<(usize, f32)>,
+}
+
+/// Select the number of clusters of a buffer by running k-means over a range
+/// of `k`.
+///
+/// For every criterion but [`Criterion::XMeans`][xmeans],
+/// [`get_kmeans`](fn.get_kmeans.html) is run once for each `k` in `k_range`.
+/// X-means visits the values of `k` reached by splitting clusters, up to the
+/// end of the range.
+///
+/// [xmeans]: enum.Criterion.html#variant.XMeans
+///
+/// - `k_range` - range of the number of clusters to evaluate.
+/// - `criterion` - criterion used to select `k`.
+/// - `max_iter` - maximum number of iterations of each run.
+/// - `converge` - threshold for convergence of each run.
+/// - `verbose` - flag for printing the score of each `k` to console.
+/// - `buf` - array of points.
+///
+/// # Panics
+///
+/// Panics if `k_range` is empty or starts at zero, or if buffer is empty.
+///
+/// ```
+/// use kmeans_colors::{select_k, Criterion};
+///
+/// // Three discs of 30 points at the corners of a right triangle
+/// let buf: Vec<[f32; 2]> = (0..90)
+///     .map(|i| {
+///         let (angle, radius) = (i as f32 * 2.4, ((i / 3) as f32 / 30.0).sqrt());
+///         let center = [[0.0, 0.0], [1000.0, 0.0], [1000.0, 1000.0]][i % 3];
+///         [center[0] + radius * angle.cos(), center[1] + radius * angle.sin()]
+///     })
+///     .collect();
+///
+/// let criteria = [
+///     Criterion::Silhouette { sample_size: 90 },
+///     Criterion::Gap { references: 10 },
+///     Criterion::XMeans,
+/// ];
+/// criteria.iter().for_each(|&criterion| {
+///     let selection = select_k(1..=6, criterion, 100, 1e-4, false, &buf);
+///     assert_eq!(selection.k, 3);
+///     assert_eq!(selection.result.centroids.len(), 3);
+/// });
+/// ```
+///
+/// ## Reference
+///
+/// Satopaa, V., Albrecht, J., Irwin, D., & Raghavan, B. (2011). Finding a
+/// "kneedle" in a haystack: Detecting knee points in system behavior.
+///
+/// Rousseeuw, P. J. (1987). Silhouettes: a graphical aid to the interpretation
+/// and validation of cluster analysis. Journal of computational and applied
+/// mathematics, 20, 53-65.
+///
+/// Tibshirani, R., Walther, G., & Hastie, T. (2001). Estimating the number of
+/// clusters in a data set via the gap statistic. Journal of the Royal
+/// Statistical Society: Series B, 63(2), 411-423.
+///
+/// Pelleg, D., & Moore, A. W. (2000). X-means: Extending k-means with
+/// efficient estimation of the number of clusters. In: ICML.
+pub fn select_k<const N: usize>(
+    k_range: RangeInclusive<usize>,
+    criterion: Criterion,
+    max_iter: usize,
+    converge: f32,
+    verbose: bool,
+    buf: &[[f32; N]],
+) -> Selection<[f32; N]> {
+    assert!(!k_range.is_empty() && *k_range.start() > 0);
+    assert!(!buf.is_empty());
+
+    let ks: Vec<usize> = k_range.clone().collect();
+    let run = || -> Vec<Kmeans<[f32; N]>> {
+        ks.iter()
+            .map(|&k: &usize| get_kmeans(k, max_iter, converge, false, buf))
+            .collect()
+    };
+
+    let (mut results, scores, best): (Vec<Kmeans<[f32; N]>>, Vec<f32>, usize) = match criterion {
+        Criterion::XMeans => return xmeans(k_range, max_iter, converge, verbose, buf),
+        Criterion::Elbow => {
+            let results: Vec<Kmeans<[f32; N]>> = run();
+            let inertias: Vec<f32> = results
+                .iter()
+                .map(|r: &Kmeans<[f32; N]>| r.inertia(buf))
+                .collect();
+            let best: usize = elbow(&inertias);
+            (results, inertias, best)
+        }
+        Criterion::Silhouette { sample_size } => {
+            let results: Vec<Kmeans<[f32; N]>> = run();
+            let silhouettes: Vec<f32> = results
+                .iter()
+                .map(|r: &Kmeans<[f32; N]>| silhouette(buf, &r.indices, sample_size))
+                .collect();
+            let best: usize = argmax(&silhouettes);
+            (results, silhouettes, best)
+        }
+        Criterion::Gap { references } => {
+            let results: Vec<Kmeans<[f32; N]>> = run();
+            let (gaps, best): (Vec<f32>, usize) =
+                gap(&ks, &results, references, max_iter, converge, buf);
+            (results, gaps, best)
+        }
+    };
+
+    if verbose {
+        ks.iter()
+            .zip(scores.iter())
+            .for_each(|(k, score): (&usize, &f32)| println!("k: {}, score: {}", k, score));
+    }
+
+    Selection {
+        k: ks[best],
+        result: results.swap_remove(best),
+        scores: ks.into_iter().zip(scores).collect(),
+    }
+}
+
+/// Index of the largest value.
+fn argmax(values: &[f32]) -> usize {
+    values
+        .iter()
+        .enumerate()
+        .fold(
+            (0, f32::MIN),
+            |(idx, max): (usize, f32), (i, &v): (usize, &f32)| {
+                if v > max {
+                    (i, v)
+                } else {
+                    (idx, max)
+                }
+            },
+        )
+        .0
+}
+
+/// Index of the elbow of a decreasing curve.
+fn elbow(inertias: &[f32]) -> usize {
+    let last: usize = inertias.len() - 1;
+    if last == 0 {
+        return 0;
+    }
+    let (first_value, last_value): (f32, f32) = (inertias[0], inertias[last]);
+    let range: f32 = (first_value - last_value).max(f32::MIN_POSITIVE);
+
+    // Distance below the chord between the normalized end points
+    let distances: Vec<f32> = inertias
+        .iter()
+        .enumerate()
+        .map(|(i, &v): (usize, &f32)| {
+            let x: f32 = i as f32 / last as f32;
+            let y: f32 = (v - last_value) / range;
+            (1.0 - x) - y
+        })
+        .collect();
+    argmax(&distances)
+}
+
+/// Calculate the mean silhouette of a clustering on about `sample_size` evenly
+/// spaced points of the buffer.
+///
+/// The silhouette of a point compares its mean distance to the other points of
+/// its cluster with its mean distance to the points of the nearest other
+/// cluster. Values range from `-1.0` to `1.0`, higher is better. Distances are
+/// the square root of [`Calculate::difference`][diff] and the cost is
+/// quadratic in `sample_size`.
+///
+/// [diff]: trait.Calculate.html#tymethod.difference
+///
+/// ```
+/// use kmeans_colors::silhouette;
+///
+/// // Each point is 1.0 from its neighbor and about 10.0 from the other pair
+/// let buf = [[0.0], [1.0], [10.0], [11.0]];
+/// let score = silhouette(&buf, &[0, 0, 1, 1], 4);
+/// let expected = (2.0 - 1.0 / 10.5 - 1.0 / 9.5) / 2.0;
+/// assert!((score - expected).abs() < 1e-6);
+///
+/// // A single cluster has no silhouette
+/// assert_eq!(silhouette(&buf, &[0; 4], 4), 0.0);
+/// ```
+pub fn silhouette<C: Calculate + Sync>(buf: &[C], indices: &[u8], sample_size: usize) -> f32 {
+    let step: usize = (buf.len() / sample_size.max(1)).max(1);
+    let sample: Vec<(&C, u8)> = buf
+        .iter()
+        .zip(indices.iter())
+        .step_by(step)
+        .map(|(p, &i): (&C, &u8)| (p, i))
+        .collect();
+    let k: usize = indices
+        .iter()
+        .map(|&i: &u8| i as usize + 1)
+        .max()
+        .unwrap_or(0);
+    if k < 2 || sample.is_empty() {
+        return 0.0;
+    }
+
+    let total: f32 = sample
+        .par_iter()
+        .map(|&(point, index): &(&C, u8)| {
+            // Mean distance to the points of each cluster
+            let mut sums: Vec<(f32, usize)> = vec![(0.0, 0); k];
+            sample.iter().for_each(|&(other, j): &(&C, u8)| {
+                let entry: &mut (f32, usize) = &mut sums[j as usize];
+                entry.0 += C::difference(point, other).sqrt();
+                entry.1 += 1;
+            });
+
+            let (own_sum, own_count): (f32, usize) = sums[index as usize];
+            if own_count < 2 {
+                return 0.0;
+            }
+            let a: f32 = own_sum / (own_count - 1) as f32;
+            let b: f32 = sums
+                .iter()
+                .enumerate()
+                .filter(|&(j, &(_, count)): &(usize, &(f32, usize))| {
+                    j != index as usize && count > 0
+                })
+                .map(|(_, &(sum, count)): (usize, &(f32, usize))| sum / count as f32)
+                .fold(f32::MAX, f32::min);
+            if b == f32::MAX {
+                return 0.0;
+            }
+            (b - a) / a.max(b).max(f32::MIN_POSITIVE)
+        })
+        .sum();
+
+    total / sample.len() as f32
+}
+
+/// Gap statistic of each result, returns the gaps and the index of the
+/// selected result.
+fn gap<const N: usize>(
+    ks: &[usize],
+    results: &[Kmeans<[f32; N]>],
+    references: usize,
+    max_iter: usize,
+    converge: f32,
+    buf: &[[f32; N]],
+) -> (Vec<f32>, usize) {
+    let (min, max): ([f32; N], [f32; N]) = buf.iter().fold(
+        ([f32::MAX; N], [f32::MIN; N]),
+        |(mut min, mut max): ([f32; N], [f32; N]), p: &[f32; N]| {
+            (0..N).for_each(|c: usize| {
+                min[c] = min[c].min(p[c]);
+                max[c] = max[c].max(p[c]);
+            });
+            (min, max)
+        },
+    );
+
+    let mut rng: SmallRng = SmallRng::from_rng(thread_rng()).unwrap();
+    let reference_bufs: Vec<Vec<[f32; N]>> = (0..references.max(1))
+        .map(|_| {
+            (0..buf.len())
+                .map(|_| {
+                    let mut point: [f32; N] = [0.0; N];
+                    (0..N).for_each(|c: usize| point[c] = rng.gen_range(min[c]..=max[c]));
+                    point
+                })
+                .collect()
+        })
+        .collect();
+
+    let (gaps, errors): (Vec<f32>, Vec<f32>) = ks
+        .iter()
+        .zip(results.iter())
+        .map(|(&k, result): (&usize, &Kmeans<[f32; N]>)| {
+            let log_w: f32 = result.inertia(buf).max(f32::MIN_POSITIVE).ln();
+            let log_refs: Vec<f32> = reference_bufs
+                .iter()
+                .map(|reference: &Vec<[f32; N]>| {
+                    get_kmeans(k, max_iter, converge, false, reference)
+                        .inertia(reference)
+                        .max(f32::MIN_POSITIVE)
+                        .ln()
+                })
+                .collect();
+            let b: f32 = log_refs.len() as f32;
+            let mean: f32 = log_refs.iter().sum::<f32>() / b;
+            let sd: f32 = (log_refs.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / b).sqrt();
+            (mean - log_w, sd * (1.0 + 1.0 / b).sqrt())
+        })
+        .unzip();
+
+    let best: usize = (0..gaps.len().saturating_sub(1))
+        .find(|&i: &usize| gaps[i] >= gaps[i + 1] - errors[i + 1])
+        .unwrap_or_else(|| argmax(&gaps));
+    (gaps, best)
+}
+
+/// Bayesian information criterion of a k-means result under the identical
+/// spherical Gaussian model, higher is better.
+fn bic<const N: usize>(buf: &[[f32; N]], result: &Kmeans<[f32; N]>) -> f32 {
+    let n: f32 = buf.len() as f32;
+    let k: usize = result.centroids.len();
+    let d: f32 = N as f32;
+
+    let mut counts: Vec<f32> = vec![0.0; k];
+    result
+        .indices
+        .iter()
+        .for_each(|&i: &u8| counts[i as usize] += 1.0);
+
+    let variance: f32 =
+        (result.inertia(buf) / (d * (n - k as f32)).max(1.0)).max(f32::MIN_POSITIVE);
+    let log_likelihood: f32 = counts
+        .iter()
+        .filter(|&&c: &&f32| c > 0.0)
+        .map(|&c: &f32| c * (c / n).ln())
+        .sum::<f32>()
+        - n * d / 2.0 * (2.0 * core::f32::consts::PI * variance).ln()
+        - d * (n - k as f32) / 2.0;
+    let parameters: f32 = (k as f32 - 1.0) + k as f32 * d + 1.0;
+
+    log_likelihood - parameters / 2.0 * n.ln()
+}
+
+/// X-means selection of the number of clusters.
+fn xmeans<const N: usize>(
+    k_range: RangeInclusive<usize>,
+    max_iter: usize,
+    converge: f32,
+    verbose: bool,
+    buf: &[[f32; N]],
+) -> Selection<[f32; N]> {
+    let k_max: usize = *k_range.end();
+    let mut centroids: Vec<[f32; N]> =
+        get_kmeans(*k_range.start(), max_iter, converge, false, buf).centroids;
+    let mut scores: Vec<(usize, f32)> = Vec::new();
+    let mut best: Option<(f32, Kmeans<[f32; N]>)> = None;
+
+    loop {
+        // Improve parameters of the current structure
+        let result: Kmeans<[f32; N]> = lloyd(
+            centroids,
+            max_iter,
+            converge,
+            false,
+            buf,
+            &SquaredEuclidean,
+            |buf: &[[f32; N]], centroids: &[[f32; N]], indices: &mut Vec<u8>| {
+                get_closest_centroid_with_metric(buf, centroids, indices, &SquaredEuclidean)
+            },
+            <[f32; N]>::recalculate_centroids,
+        );
+        let k: usize = result.centroids.len();
+        let score: f32 = bic(buf, &result);
+        if verbose {
+            println!("k: {}, score: {}", k, score);
+        }
+        scores.push((k, score));
+
+        // Improve structure by splitting clusters whose BIC improves
+        let mut split: Vec<[f32; N]> = Vec::with_capacity(k_max);
+        result
+            .centroids
+            .iter()
+            .enumerate()
+            .for_each(|(j, centroid): (usize, &[f32; N])| {
+                let members: Vec<[f32; N]> = buf
+                    .iter()
+                    .zip(result.indices.iter())
+                    .filter(|&(_, &i): &(&[f32; N], &u8)| i as usize == j)
+                    .map(|(p, _): (&[f32; N], &u8)| *p)
+                    .collect();
+                let remaining: usize = k - (j + 1);
+                if members.len() > 2 && split.len() + remaining + 2 <= k_max {
+                    let parent: Kmeans<[f32; N]> = Kmeans {
+                        score: 0.0,
+                        centroids: vec![*centroid],
+                        indices: vec![0; members.len()],
+                        locked: Vec::new(),
+                    };
+                    let children: Kmeans<[f32; N]> =
+                        get_kmeans(2, max_iter, converge, false, &members);
+                    if children.centroids.len() == 2
+                        && bic(&members, &children) > bic(&members, &parent)
+                    {
+                        split.extend(children.centroids);
+                        return;
+                    }
+                }
+                split.push(*centroid);
+            });
+
+        let improved: bool = split.len() > k;
+        match best {
+            Some((best_score, _)) if best_score >= score => {}
+            _ => best = Some((score, result)),
+        }
+        if !improved {
+            break;
+        }
+        centroids = split;
+    }
+
+    let (_, result): (f32, Kmeans<[f32; N]>) = best.unwrap();
+    Selection {
+        k: result.centroids.len(),
+        result,
+        scores,
+    }
+}
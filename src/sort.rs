@@ -1,3 +1,5 @@
+use rayon::prelude::*;
+
 /// Struct containing a centroid, its percentage within a buffer, and the
 /// centroid's index.
 #[derive(Clone, Debug, Default)]
@@ -8,6 +10,124 @@ pub struct CentroidData<C: crate::Calculate> {
     pub percentage: f32,
     /// The centroid's index.
     pub index: u8,
+    /// The number of points of the buffer indexed to the centroid.
+    pub(crate) count: usize,
+}
+
+impl<C: crate::Calculate> CentroidData<C> {
+    /// Create a new `CentroidData` for a centroid indexed by `count` points.
+    pub fn new(centroid: C, percentage: f32, index: u8, count: usize) -> Self {
+        CentroidData {
+            centroid,
+            percentage,
+            index,
+            count,
+        }
+    }
+
+    /// The number of points of the buffer indexed to the centroid.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl<C: crate::Calculate + Clone> CentroidData<C> {
+    /// Count the points indexed to each centroid and calculate the share of
+    /// the buffer each centroid represents. Returns a Vec of `CentroidData` in
+    /// centroid order.
+    pub fn from_indices(centroids: &[C], indices: &[u8]) -> Vec<CentroidData<C>> {
+        let mut counts: Vec<usize> = vec![0; centroids.len()];
+        indices.iter().for_each(|&i: &u8| {
+            if let Some(count) = counts.get_mut(i as usize) {
+                *count += 1;
+            }
+        });
+
+        let total: f32 = indices.len().max(1) as f32;
+        centroids
+            .iter()
+            .zip(counts)
+            .enumerate()
+            .map(
+                |(i, (centroid, count)): (usize, (&C, usize))| CentroidData {
+                    centroid: centroid.clone(),
+                    percentage: count as f32 / total,
+                    index: i as u8,
+                    count,
+                },
+            )
+            .collect()
+    }
+}
+
+/// Ordering of a palette of centroids.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Perceptual lightness `L*` from darkest to lightest.
+    Lightness,
+    /// Hue angle in CIELAB, with near-gray colors grouped first from darkest to
+    /// lightest.
+    Hue,
+    /// Share of the buffer from most to least common.
+    Share,
+    /// Chroma in CIELAB from the most muted to the most vivid.
+    Chroma,
+    /// Path from the darkest color that steps to the nearest remaining color
+    /// by `ΔE*76`, improved with 2-opt, which keeps adjacent colors similar for
+    /// smooth palette strips.
+    NearestNeighbor,
+    /// HSL saturation, then hue, then lightness, the order of
+    /// [`Sort::sort_colors`](trait.Sort.html#tymethod.sort_colors).
+    Hsl,
+}
+
+/// Invert a permutation of centroid indices, mapping each old index to its
+/// new position.
+fn invert(permutation: &[u8]) -> Vec<u8> {
+    let mut inverse: Vec<u8> = (0..=u8::MAX).collect();
+    permutation
+        .iter()
+        .enumerate()
+        .for_each(|(new, &old): (usize, &u8)| inverse[old as usize] = new as u8);
+    inverse
+}
+
+/// Rewrite an indexed buffer after its centroids were reordered by
+/// `permutation`, where the `n`-th element of the permutation is the old
+/// index of the centroid now at position `n`. Indices missing from the
+/// permutation are left unchanged.
+pub fn relabel(indices: &mut [u8], permutation: &[u8]) {
+    let inverse: Vec<u8> = invert(permutation);
+    indices
+        .par_iter_mut()
+        .for_each(|i: &mut u8| *i = inverse[*i as usize]);
+}
+
+/// Rewrite the `index` of each `CentroidData` after its centroids were
+/// reordered by `permutation`, so the data keeps pointing at the same colors
+/// of the reordered palette.
+///
+/// ```
+/// use kmeans_colors::{get_kmeans, relabel_data, sort_permutation, Sort, SortOrder};
+///
+/// let rgb = [[255.0; 3], [0.0; 3], [250.0; 3], [5.0; 3], [3.0; 3]];
+/// let mut result = get_kmeans(2, 20, 0.0, false, &rgb);
+/// let mut data = <[f32; 3]>::sort_indexed_colors(&result.centroids, &result.indices);
+///
+/// let permutation = sort_permutation(&result.centroids, &result.indices, SortOrder::Share);
+/// result.reorder(&permutation);
+/// relabel_data(&mut data, &permutation);
+///
+/// // The most common color is now first and the data still matches it
+/// assert_eq!(result.centroids[0], [8.0 / 3.0; 3]);
+/// data.iter().for_each(|d| assert_eq!(result.centroids[d.index as usize], d.centroid));
+/// assert_eq!(data[0].index, 0);
+/// assert_eq!(data[0].count(), 3);
+/// ```
+pub fn relabel_data<C: crate::Calculate>(data: &mut [CentroidData<C>], permutation: &[u8]) {
+    let inverse: Vec<u8> = invert(permutation);
+    data.iter_mut()
+        .for_each(|d: &mut CentroidData<C>| d.index = inverse[d.index as usize]);
 }
 
 /// A trait for sorting indexed k-means colors.
@@ -15,8 +135,20 @@ pub trait Sort: Sized + crate::Calculate {
     /// Returns the centroid with the largest percentage.
     fn get_dominant_color(data: &[CentroidData<Self>]) -> Option<Self>;
 
-    /// Sorts centroids by luminosity and calculates the percentage of each
-    /// color in the buffer. Returns a Vec of `CentroidData` sorted from darkest
-    /// to lightest.
+    /// Calculates the percentage of each color in the buffer and sorts the
+    /// centroids by lightness. Returns a Vec of `CentroidData` sorted from
+    /// darkest to lightest. The `[f32; 3]` implementation sorts by CIELAB
+    /// lightness `L*`, the default implementation keeps the centroid order of
+    /// [`CentroidData::from_indices`][from_indices].
+    ///
+    /// [from_indices]: struct.CentroidData.html#method.from_indices
+    fn sort_indexed_colors(centroids: &[Self], indices: &[u8]) -> Vec<CentroidData<Self>>
+    where
+        Self: Clone,
+    {
+        CentroidData::from_indices(centroids, indices)
+    }
+
+    /// Sorts centroids by saturation, then hue, then lightness.
     fn sort_colors(centroids: &[Self]) -> Vec<Self>;
 }
@@ -0,0 +1,112 @@
+use rand::{rngs::SmallRng, thread_rng, Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::kmeans::{get_closest_centroid_with_metric, lloyd, Kmeans};
+use crate::metric::Cosine;
+
+/// Scale a point to unit length. Points with zero magnitude are left
+/// unchanged.
+#[inline]
+pub fn normalize<const N: usize>(point: &mut [f32; N]) {
+    let norm: f32 = point.iter().map(|v: &f32| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        point.iter_mut().for_each(|v: &mut f32| *v /= norm);
+    }
+}
+
+/// k-means++ centroid initialization using cosine distance. The chosen
+/// centroids are normalized to unit length.
+///
+/// # Panics
+///
+/// Panics if buffer is empty.
+pub fn init_plus_plus_spherical<const N: usize>(
+    k: usize,
+    rng: &mut impl Rng,
+    buf: &[[f32; N]],
+    centroids: &mut Vec<[f32; N]>,
+) {
+    crate::plus_plus::init_plus_plus_with_metric(k, rng, buf, centroids, &Cosine);
+    centroids.par_iter_mut().for_each(normalize);
+}
+
+/// Find the spherical k-means centroids of a buffer. Takes the same arguments
+/// as [`get_kmeans`](fn.get_kmeans.html).
+///
+/// Spherical k-means is suited to clustering normalized embeddings. Points are
+/// assigned to the centroid with the highest cosine similarity and centroids
+/// are recalculated as the mean of their points re-normalized to unit length.
+/// The `score` is the sum of cosine distances the centroids moved in the final
+/// iteration.
+///
+/// Flat buffers of rows can be converted with `chunks_exact(N)` and
+/// `try_into` before clustering.
+///
+/// ## Reference
+///
+/// Dhillon, I. S., & Modha, D. S. (2001). Concept decompositions for large
+/// sparse text data using clustering. Machine learning, 42(1), 143-175.
+pub fn get_kmeans_spherical<const N: usize>(
+    k: usize,
+    max_iter: usize,
+    converge: f32,
+    verbose: bool,
+    buf: &[[f32; N]],
+) -> Kmeans<[f32; N]> {
+    // Initialize the random centroids
+    let mut rng: SmallRng = SmallRng::from_rng(thread_rng()).unwrap();
+    let mut centroids: Vec<[f32; N]> = Vec::with_capacity(k);
+    init_plus_plus_spherical(k, &mut rng, buf, &mut centroids);
+
+    lloyd(
+        centroids,
+        max_iter,
+        converge,
+        verbose,
+        buf,
+        &Cosine,
+        |buf: &[[f32; N]], centroids: &[[f32; N]], indices: &mut Vec<u8>| {
+            get_closest_centroid_with_metric(buf, centroids, indices, &Cosine)
+        },
+        |buf: &[[f32; N]], centroids: &mut [[f32; N]], indices: &[u8]| {
+            recalculate_centroids_spherical(&mut rng, buf, centroids, indices)
+        },
+    )
+}
+
+/// Recalculate each centroid as the normalized mean of its points. Centroids
+/// without points are re-initialized with a random point of the buffer, so
+/// they stay on the unit sphere of the data instead of being drawn from the
+/// color range.
+fn recalculate_centroids_spherical<const N: usize>(
+    rng: &mut impl Rng,
+    buf: &[[f32; N]],
+    centroids: &mut [[f32; N]],
+    indices: &[u8],
+) {
+    let mut sums: Vec<[f32; N]> = vec![[0.0; N]; centroids.len()];
+    let mut counts: Vec<usize> = vec![0; centroids.len()];
+    buf.iter()
+        .zip(indices.iter())
+        .for_each(|(point, &index): (&[f32; N], &u8)| {
+            sums[index as usize]
+                .iter_mut()
+                .zip(point.iter())
+                .for_each(|(s, p): (&mut f32, &f32)| *s += p);
+            counts[index as usize] += 1;
+        });
+
+    centroids
+        .iter_mut()
+        .zip(sums.into_iter().zip(counts.iter()))
+        .for_each(
+            |(centroid, (sum, &count)): (&mut [f32; N], ([f32; N], &usize))| {
+                *centroid = if count > 0 {
+                    sum
+                } else {
+                    buf[rng.gen_range(0..buf.len())]
+                };
+                normalize(centroid);
+            },
+        );
+}
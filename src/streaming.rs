@@ -0,0 +1,140 @@
+use rand::{rngs::SmallRng, thread_rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::kmeans::{closest_centroid, Calculate, Kmeans};
+
+/// Incremental k-means over a buffer supplied in chunks.
+///
+/// Each chunk is assigned to the current centroids in parallel, then every
+/// point moves its centroid towards it with a per-centroid learning rate of
+/// `1 / count`, so each centroid is the running mean of the points assigned
+/// to it. The first centroids are chosen with k-means++ from the first chunks.
+///
+/// After all chunks have been seen, [`finalize`](#method.finalize) produces a
+/// `Kmeans` result and [`label`](#method.label) indexes the chunks in a second
+/// pass.
+///
+/// ```
+/// use kmeans_colors::StreamingKmeans;
+///
+/// let chunks = [
+///     [[0.0, 0.0, 0.0], [255.0, 255.0, 255.0]],
+///     [[2.0, 2.0, 2.0], [253.0, 253.0, 253.0]],
+/// ];
+/// let mut streaming = StreamingKmeans::new(2);
+/// chunks.iter().for_each(|chunk| streaming.update(chunk));
+///
+/// let result = streaming.finalize();
+/// let indices = streaming.label(&chunks[1]);
+/// assert_ne!(indices[0], indices[1]);
+/// assert_eq!(result.centroids[indices[0] as usize], [1.0; 3]);
+///
+/// // A new model has no centroids to index with
+/// assert!(StreamingKmeans::new(2).label(&chunks[0]).is_empty());
+/// ```
+///
+/// ## Reference
+///
+/// MacQueen, J. (1967). Some methods for classification and analysis of
+/// multivariate observations. In: Proceedings of the fifth Berkeley symposium
+/// on mathematical statistics and probability.
+///
+/// Sculley, D. (2010). Web-scale k-means clustering. In: Proceedings of the
+/// 19th international conference on World wide web.
+#[derive(Clone, Debug)]
+pub struct StreamingKmeans<const N: usize> {
+    k: usize,
+    centroids: Vec<[f32; N]>,
+    counts: Vec<u64>,
+    score: f32,
+}
+
+impl<const N: usize> StreamingKmeans<N> {
+    /// Create a new `StreamingKmeans` for `k` clusters.
+    pub fn new(k: usize) -> Self {
+        StreamingKmeans {
+            k,
+            centroids: Vec::with_capacity(k),
+            counts: Vec::with_capacity(k),
+            score: f32::MAX,
+        }
+    }
+
+    /// Current centroids, fewer than `k` until enough distinct points have
+    /// been seen.
+    pub fn centroids(&self) -> &[[f32; N]] {
+        &self.centroids
+    }
+
+    /// Number of points assigned to each centroid so far.
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Update the centroids with a chunk of points.
+    pub fn update(&mut self, chunk: &[[f32; N]]) {
+        if chunk.is_empty() {
+            return;
+        }
+
+        // Seed missing centroids from the chunk
+        if self.centroids.len() < self.k {
+            let mut rng: SmallRng = SmallRng::from_rng(thread_rng()).unwrap();
+            crate::plus_plus::extend_plus_plus(
+                self.k,
+                &mut rng,
+                chunk,
+                &mut self.centroids,
+                &|c1: &[f32; N], c2: &[f32; N]| <[f32; N]>::difference(c1, c2),
+            );
+            self.counts.resize(self.centroids.len(), 0);
+        }
+
+        let old_centroids: Vec<[f32; N]> = self.centroids.clone();
+        let indices: Vec<u8> = self.label(chunk);
+        chunk
+            .iter()
+            .zip(indices.iter())
+            .for_each(|(point, &index): (&[f32; N], &u8)| {
+                let count: &mut u64 = &mut self.counts[index as usize];
+                *count += 1;
+                let rate: f32 = 1.0 / *count as f32;
+                self.centroids[index as usize]
+                    .iter_mut()
+                    .zip(point.iter())
+                    .for_each(|(c, p): (&mut f32, &f32)| *c += rate * (p - *c));
+            });
+
+        self.score = <[f32; N]>::check_loop(&self.centroids, &old_centroids);
+    }
+
+    /// Index each point of a chunk with its nearest centroid. Returns an empty
+    /// Vec if no points have been seen.
+    pub fn label(&self, chunk: &[[f32; N]]) -> Vec<u8> {
+        if self.centroids.is_empty() {
+            return Vec::new();
+        }
+        chunk
+            .par_iter()
+            .map(|point: &[f32; N]| {
+                closest_centroid(point, &self.centroids, &|c1: &[f32; N], c2: &[f32; N]| {
+                    <[f32; N]>::difference(c1, c2)
+                })
+                .0 as u8
+            })
+            .collect()
+    }
+
+    /// Produce a `Kmeans` result from the current centroids. The `score` is
+    /// the movement of the centroids during the last update and the indices
+    /// are empty, use [`label`](#method.label) for a second pass over the
+    /// chunks.
+    pub fn finalize(&self) -> Kmeans<[f32; N]> {
+        Kmeans {
+            score: self.score,
+            centroids: self.centroids.clone(),
+            indices: Vec::new(),
+            locked: Vec::new(),
+        }
+    }
+}
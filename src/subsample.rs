@@ -0,0 +1,135 @@
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use crate::kmeans::{get_closest_centroid_with_metric, get_kmeans, Calculate, Kmeans};
+
+/// Method for choosing a deterministic subset of a buffer.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Subsample {
+    /// Every `n`-th point, starting from the first.
+    Stride(usize),
+    /// A uniformly random `fraction` of the points, in buffer order.
+    Random {
+        /// Share of the points to keep, between `0.0` and `1.0`.
+        fraction: f32,
+        /// Seed for the random number generator.
+        seed: u64,
+    },
+    /// One random point from each `cell` by `cell` block of an image buffer
+    /// that is `width` points wide, covering the image evenly.
+    Stratified {
+        /// Width of the image in points.
+        width: usize,
+        /// Side length of each block in points.
+        cell: usize,
+        /// Seed for the random number generator.
+        seed: u64,
+    },
+}
+
+/// Choose a subset of a buffer. The same method and buffer always produce the
+/// same subset. A stride or cell of `0` is treated as `1`.
+///
+/// ```
+/// use kmeans_colors::{subsample, Subsample};
+///
+/// let buf: Vec<usize> = (0..16).collect();
+/// assert_eq!(subsample(&buf, Subsample::Stride(5)), [0, 5, 10, 15]);
+///
+/// // Seeded methods repeat their subset
+/// let random = Subsample::Random { fraction: 0.25, seed: 7 };
+/// assert_eq!(subsample(&buf, random).len(), 4);
+/// assert_eq!(subsample(&buf, random), subsample(&buf, random));
+///
+/// // One point from each 2x2 block of a 4x4 image
+/// let stratified = subsample(&buf, Subsample::Stratified { width: 4, cell: 2, seed: 7 });
+/// let blocks = [[0, 1, 4, 5], [2, 3, 6, 7], [8, 9, 12, 13], [10, 11, 14, 15]];
+/// assert_eq!(stratified.len(), 4);
+/// stratified
+///     .iter()
+///     .zip(blocks.iter())
+///     .for_each(|(point, block)| assert!(block.contains(point)));
+/// ```
+pub fn subsample<C: Clone>(buf: &[C], method: Subsample) -> Vec<C> {
+    match method {
+        Subsample::Stride(n) => buf.iter().step_by(n.max(1)).cloned().collect(),
+        Subsample::Random { fraction, seed } => {
+            let mut rng: SmallRng = SmallRng::seed_from_u64(seed);
+            let amount: usize =
+                ((fraction.clamp(0.0, 1.0) * buf.len() as f32).ceil() as usize).min(buf.len());
+            let mut chosen: Vec<usize> =
+                rand::seq::index::sample(&mut rng, buf.len(), amount).into_vec();
+            chosen.sort_unstable();
+            chosen.iter().map(|&i: &usize| buf[i].clone()).collect()
+        }
+        Subsample::Stratified { width, cell, seed } => {
+            let mut rng: SmallRng = SmallRng::seed_from_u64(seed);
+            let width: usize = width.clamp(1, buf.len().max(1));
+            let cell: usize = cell.max(1);
+            let height: usize = buf.len().div_ceil(width);
+
+            let mut sample: Vec<C> = Vec::with_capacity(buf.len() / (cell * cell) + 1);
+            (0..height).step_by(cell).for_each(|y: usize| {
+                (0..width).step_by(cell).for_each(|x: usize| {
+                    let h: usize = cell.min(height - y);
+                    let w: usize = cell.min(width - x);
+                    let i: usize = (y + rng.gen_range(0..h)) * width + x + rng.gen_range(0..w);
+                    if let Some(point) = buf.get(i) {
+                        sample.push(point.clone());
+                    }
+                });
+            });
+            sample
+        }
+    }
+}
+
+/// Find the k-means centroids of a subset of a buffer and index the full
+/// buffer. Takes the same arguments as [`get_kmeans`](fn.get_kmeans.html)
+/// along with the subsampling method.
+///
+/// Centroids are fit on the points chosen by
+/// [`subsample`](fn.subsample.html), then every point of the buffer is indexed
+/// to its nearest centroid in a single assignment pass. This is an
+/// alternative to scaling down an image before calculating its palette.
+///
+/// # Panics
+///
+/// Panics if the subset is empty.
+///
+/// ```
+/// use kmeans_colors::{get_kmeans_subsampled, Subsample};
+///
+/// let buf = [[0.0; 3], [1.0; 3], [2.0; 3], [3.0; 3], [250.0; 3], [251.0; 3], [252.0; 3], [253.0; 3]];
+/// let result = get_kmeans_subsampled(2, Subsample::Stride(2), 20, 0.0, false, &buf);
+///
+/// // Centroids are fit on every other point and the full buffer is indexed
+/// let mut centroids = result.centroids.clone();
+/// centroids.sort_by(|a, b| a[0].total_cmp(&b[0]));
+/// assert_eq!(centroids, [[1.0; 3], [251.0; 3]]);
+/// assert_eq!(result.indices[..4], [result.indices[0]; 4]);
+/// assert_eq!(result.indices[4..], [result.indices[4]; 4]);
+/// assert_ne!(result.indices[0], result.indices[4]);
+/// ```
+pub fn get_kmeans_subsampled<C: Calculate + Clone + Sync + Send>(
+    k: usize,
+    method: Subsample,
+    max_iter: usize,
+    converge: f32,
+    verbose: bool,
+    buf: &[C],
+) -> Kmeans<C> {
+    let sample: Vec<C> = subsample(buf, method);
+    let result: Kmeans<C> = get_kmeans(k, max_iter, converge, verbose, &sample);
+
+    let mut indices: Vec<u8> = Vec::with_capacity(buf.len());
+    get_closest_centroid_with_metric(buf, &result.centroids, &mut indices, &|c1: &C, c2: &C| {
+        C::difference(c1, c2)
+    });
+
+    Kmeans {
+        score: result.score,
+        centroids: result.centroids,
+        indices,
+        locked: Vec::new(),
+    }
+}
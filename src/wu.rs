@@ -0,0 +1,273 @@
+use rayon::prelude::*;
+
+use crate::kmeans::Kmeans;
+
+/// Number of histogram bins along each channel, plus one for the zero row of
+/// the cumulative moments.
+const SIDE: usize = 33;
+
+/// Cumulative moments of the color histogram.
+struct Moments {
+    weight: Vec<f64>,
+    sum: Vec<[f64; 3]>,
+    squares: Vec<f64>,
+}
+
+/// A box of histogram cells, exclusive of its lower bounds and inclusive of
+/// its upper bounds.
+#[derive(Copy, Clone, Debug)]
+struct CellBox {
+    lower: [usize; 3],
+    upper: [usize; 3],
+}
+
+#[inline]
+fn cell(r: usize, g: usize, b: usize) -> usize {
+    (r * SIDE + g) * SIDE + b
+}
+
+impl Moments {
+    /// Weight, channel sums, and sum of squared channels of the points in a
+    /// box.
+    fn volume(&self, cells: &CellBox) -> (f64, [f64; 3], f64) {
+        let [r0, g0, b0]: [usize; 3] = cells.lower;
+        let [r1, g1, b1]: [usize; 3] = cells.upper;
+        let corners: [(usize, f64); 8] = [
+            (cell(r1, g1, b1), 1.0),
+            (cell(r1, g1, b0), -1.0),
+            (cell(r1, g0, b1), -1.0),
+            (cell(r1, g0, b0), 1.0),
+            (cell(r0, g1, b1), -1.0),
+            (cell(r0, g1, b0), 1.0),
+            (cell(r0, g0, b1), 1.0),
+            (cell(r0, g0, b0), -1.0),
+        ];
+        corners.iter().fold(
+            (0.0, [0.0; 3], 0.0),
+            |(w, s, q): (f64, [f64; 3], f64), &(i, sign): &(usize, f64)| {
+                (
+                    w + sign * self.weight[i],
+                    [
+                        s[0] + sign * self.sum[i][0],
+                        s[1] + sign * self.sum[i][1],
+                        s[2] + sign * self.sum[i][2],
+                    ],
+                    q + sign * self.squares[i],
+                )
+            },
+        )
+    }
+
+    /// Weighted sum of squared errors of the points in a box.
+    fn variance(&self, cells: &CellBox) -> f64 {
+        let (w, s, q): (f64, [f64; 3], f64) = self.volume(cells);
+        if w <= 0.0 {
+            return 0.0;
+        }
+        (q - s.iter().map(|v: &f64| v * v).sum::<f64>() / w).max(0.0)
+    }
+
+    /// Find the cut of a box which minimizes the summed variance of its two
+    /// halves, returning `None` if every cut leaves a half empty.
+    fn cut(&self, cells: &CellBox) -> Option<(CellBox, CellBox)> {
+        let (whole_w, whole_s, _): (f64, [f64; 3], f64) = self.volume(cells);
+        let spread =
+            |w: f64, s: [f64; 3]| -> f64 { s.iter().map(|v: &f64| v * v).sum::<f64>() / w };
+
+        // Maximizing the spread of the halves minimizes their variance
+        let mut best: Option<(f64, usize, usize)> = None;
+        (0..3).for_each(|axis: usize| {
+            (cells.lower[axis] + 1..cells.upper[axis]).for_each(|at: usize| {
+                let mut half: CellBox = *cells;
+                half.upper[axis] = at;
+                let (w, s, _): (f64, [f64; 3], f64) = self.volume(&half);
+                let other_w: f64 = whole_w - w;
+                if w <= 0.0 || other_w <= 0.0 {
+                    return;
+                }
+                let other_s: [f64; 3] = [whole_s[0] - s[0], whole_s[1] - s[1], whole_s[2] - s[2]];
+                let value: f64 = spread(w, s) + spread(other_w, other_s);
+                if !matches!(best, Some((max, _, _)) if max >= value) {
+                    best = Some((value, axis, at));
+                }
+            });
+        });
+
+        best.map(|(_, axis, at): (f64, usize, usize)| {
+            let (mut first, mut second): (CellBox, CellBox) = (*cells, *cells);
+            first.upper[axis] = at;
+            second.lower[axis] = at;
+            (first, second)
+        })
+    }
+}
+
+/// Quantize a buffer of colors into at most `k` colors with Wu's
+/// variance-minimizing quantizer.
+///
+/// The colors are binned into a 32x32x32 histogram spanning their bounding
+/// box, along with cumulative moments that give the variance of any box of
+/// bins in constant time. The box with the largest variance is repeatedly cut
+/// where it most reduces the summed variance of its halves, until there are
+/// `k` boxes or no box can be cut. The centroids are the weighted means of the
+/// colors in each box and each color is indexed to its box, so the result can
+/// be used with [`MapColor`](trait.MapColor.html) directly. The result is
+/// deterministic, and its centroids can seed
+/// [`get_kmeans_hamerly_seeded`](fn.get_kmeans_hamerly_seeded.html).
+///
+/// The `score` is the total weighted sum of squared errors of the boxes.
+///
+/// - `k` - maximum number of colors.
+/// - `weights` - optional weight of each color, such as histogram counts.
+/// - `buf` - array of colors.
+///
+/// # Panics
+///
+/// Panics if buffer is empty, if `k` is zero, or if `weights` has a different
+/// length than the buffer.
+///
+/// ```
+/// use kmeans_colors::{get_kmeans_hamerly_seeded, get_wu, MapColor};
+///
+/// let buf = [[0.0; 3], [1.0; 3], [2.0; 3], [200.0; 3], [201.0; 3], [202.0; 3], [255.0; 3]];
+/// let result = get_wu(3, None, &buf);
+/// assert_eq!(result.centroids, [[1.0; 3], [201.0; 3], [255.0; 3]]);
+/// assert_eq!(result.indices, [0, 0, 0, 1, 1, 1, 2]);
+/// assert_eq!(result.score, 12.0);
+///
+/// // The indices map the buffer onto the palette
+/// let colors = <[f32; 3]>::map_indices_to_centroids(&result.centroids, &result.indices);
+/// assert_eq!(colors[4], [201.0; 3]);
+///
+/// // and the palette seeds k-means, which has nothing left to improve
+/// let refined = get_kmeans_hamerly_seeded(result.centroids, 20, 0.0, false, &buf);
+/// assert_eq!(refined.centroids, [[1.0; 3], [201.0; 3], [255.0; 3]]);
+/// assert_eq!(refined.indices, result.indices);
+/// ```
+///
+/// ## Reference
+///
+/// Wu, X. (1991). Efficient statistical computations for optimal color
+/// quantization. In: Graphics Gems II, 126-133.
+pub fn get_wu(k: usize, weights: Option<&[f32]>, buf: &[[f32; 3]]) -> Kmeans<[f32; 3]> {
+    assert!(!buf.is_empty());
+    assert!(k > 0);
+    if let Some(weights) = weights {
+        assert_eq!(weights.len(), buf.len());
+    }
+    let weight = |i: usize| -> f64 { weights.map_or(1.0, |w: &[f32]| w[i] as f64) };
+
+    // Bin each color within the bounding box of the buffer
+    let (min, max): ([f32; 3], [f32; 3]) = buf.iter().fold(
+        ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]),
+        |(mut min, mut max): ([f32; 3], [f32; 3]), color: &[f32; 3]| {
+            (0..3).for_each(|c: usize| {
+                min[c] = min[c].min(color[c]);
+                max[c] = max[c].max(color[c]);
+            });
+            (min, max)
+        },
+    );
+    let bins: Vec<usize> = buf
+        .par_iter()
+        .map(|color: &[f32; 3]| {
+            let [r, g, b]: [usize; 3] = [0, 1, 2].map(|c: usize| {
+                let range: f32 = max[c] - min[c];
+                if range > 0.0 {
+                    1 + (((color[c] - min[c]) / range * (SIDE - 1) as f32) as usize).min(SIDE - 2)
+                } else {
+                    1
+                }
+            });
+            cell(r, g, b)
+        })
+        .collect();
+
+    // Histogram of the moments, then summed into cumulative moments
+    let mut moments: Moments = Moments {
+        weight: vec![0.0; SIDE * SIDE * SIDE],
+        sum: vec![[0.0; 3]; SIDE * SIDE * SIDE],
+        squares: vec![0.0; SIDE * SIDE * SIDE],
+    };
+    buf.iter().zip(bins.iter()).enumerate().for_each(
+        |(i, (color, &bin)): (usize, (&[f32; 3], &usize))| {
+            let w: f64 = weight(i);
+            moments.weight[bin] += w;
+            (0..3).for_each(|c: usize| moments.sum[bin][c] += w * color[c] as f64);
+            moments.squares[bin] +=
+                w * color.iter().map(|&v: &f32| (v as f64).powi(2)).sum::<f64>();
+        },
+    );
+    (0..3).for_each(|axis: usize| {
+        let step: usize = [SIDE * SIDE, SIDE, 1][axis];
+        (0..SIDE * SIDE * SIDE).for_each(|i: usize| {
+            // Skip the first cell along the axis
+            if i % (step * SIDE) < step {
+                return;
+            }
+            moments.weight[i] += moments.weight[i - step];
+            let previous: [f64; 3] = moments.sum[i - step];
+            (0..3).for_each(|c: usize| moments.sum[i][c] += previous[c]);
+            moments.squares[i] += moments.squares[i - step];
+        });
+    });
+
+    // Repeatedly cut the box with the largest variance
+    let mut boxes: Vec<CellBox> = vec![CellBox {
+        lower: [0; 3],
+        upper: [SIDE - 1; 3],
+    }];
+    let mut variances: Vec<f64> = vec![moments.variance(&boxes[0])];
+    while boxes.len() < k {
+        let chosen: Option<usize> = variances
+            .iter()
+            .enumerate()
+            .filter(|&(_, &v): &(usize, &f64)| v > 0.0)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| i);
+        let i: usize = match chosen {
+            Some(i) => i,
+            None => break,
+        };
+        match moments.cut(&boxes[i]) {
+            Some((first, second)) => {
+                boxes[i] = first;
+                variances[i] = moments.variance(&first);
+                boxes.push(second);
+                variances.push(moments.variance(&second));
+            }
+            None => variances[i] = 0.0,
+        }
+    }
+
+    // Label each histogram cell with its box
+    let mut tags: Vec<u8> = vec![0; SIDE * SIDE * SIDE];
+    boxes
+        .iter()
+        .enumerate()
+        .for_each(|(label, cells): (usize, &CellBox)| {
+            (cells.lower[0] + 1..=cells.upper[0]).for_each(|r: usize| {
+                (cells.lower[1] + 1..=cells.upper[1]).for_each(|g: usize| {
+                    (cells.lower[2] + 1..=cells.upper[2]).for_each(|b: usize| {
+                        tags[cell(r, g, b)] = label as u8;
+                    });
+                });
+            });
+        });
+
+    Kmeans {
+        score: boxes
+            .iter()
+            .map(|cells: &CellBox| moments.variance(cells))
+            .sum::<f64>() as f32,
+        centroids: boxes
+            .iter()
+            .map(|cells: &CellBox| {
+                let (w, s, _): (f64, [f64; 3], f64) = moments.volume(cells);
+                s.map(|v: f64| (v / w.max(f64::MIN_POSITIVE)) as f32)
+            })
+            .collect(),
+        indices: bins.par_iter().map(|&bin: &usize| tags[bin]).collect(),
+        locked: Vec::new(),
+    }
+}